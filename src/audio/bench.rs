@@ -0,0 +1,124 @@
+// Compares the CPU time spent per session on the PCM and Opus output paths
+//
+// The PCM path resamples in the sink and has songbird encode every frame, while the Opus path
+// encodes in the sink and lets songbird pass the frames through untouched.
+//
+// Run with: cargo test --release bench_output_paths -- --ignored --nocapture
+
+use std::{
+  io::Read,
+  time::{Duration, Instant},
+};
+
+use audiopus::{coder::Encoder, Application, Channels, SampleRate};
+use librespot::playback::{
+  audio_backend::Sink, convert::Converter, decoder::AudioPacket, SAMPLE_RATE,
+};
+
+use super::{
+  encoder::FRAME_SAMPLES, settings::AudioSettings, stream::Stream, telemetry::Telemetry, StreamSink,
+};
+
+/// The amount of audio pushed through each path
+const DURATION_SECS: usize = 60;
+
+/// The amount of interleaved samples in a single packet coming out of librespot's decoder
+const PACKET_SAMPLES: usize = 2048 * 2;
+
+/// Generate a 440 Hz stereo sine wave, split up into librespot sized packets
+fn packets() -> Vec<Vec<f64>> {
+  let step = 440.0 * std::f64::consts::TAU / SAMPLE_RATE as f64;
+
+  let samples = (0..SAMPLE_RATE as usize * DURATION_SECS)
+    .flat_map(|i| {
+      let sample = (step * i as f64).sin() * 0.5;
+      [sample, sample]
+    })
+    .collect::<Vec<_>>();
+
+  samples
+    .chunks(PACKET_SAMPLES)
+    .map(|chunk| chunk.to_vec())
+    .collect()
+}
+
+/// Push all packets through a sink, handing every packet's output to `consume`
+///
+/// Returns the time spent in the sink and in `consume` combined
+fn run(stream: Stream, mut consume: impl FnMut(&mut Stream)) -> Duration {
+  let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+  let mut sink = StreamSink::new(stream.clone(), tx, AudioSettings::new());
+  let mut converter = Converter::new(None);
+  let mut reader = stream;
+
+  let mut elapsed = Duration::ZERO;
+
+  for packet in packets() {
+    let start = Instant::now();
+
+    sink
+      .write(AudioPacket::Samples(packet), &mut converter)
+      .expect("to write the packet");
+    consume(&mut reader);
+
+    elapsed += start.elapsed();
+  }
+
+  elapsed
+}
+
+/// Songbird's work for raw PCM: encode every frame
+fn consume_pcm(encoder: &Encoder) -> impl FnMut(&mut Stream) + '_ {
+  let mut bytes = vec![0u8; FRAME_SAMPLES * 4];
+  let mut packet = [0u8; 1275];
+
+  move |stream| {
+    while stream.buffered() >= bytes.len() {
+      stream.read_exact(&mut bytes).expect("to read a frame");
+
+      let samples = bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect::<Vec<_>>();
+
+      encoder
+        .encode_float(&samples, &mut packet)
+        .expect("to encode a frame");
+    }
+  }
+}
+
+/// Songbird's work for Opus passthrough: read the frame and forward it
+fn consume_opus(stream: &mut Stream) {
+  let mut packet = [0u8; 1275];
+
+  while stream.buffered() > 0 {
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header).expect("to read a header");
+
+    let size = i16::from_le_bytes(header) as usize;
+    stream
+      .read_exact(&mut packet[..size])
+      .expect("to read a frame");
+  }
+}
+
+#[test]
+#[ignore]
+fn bench_output_paths() {
+  let encoder = Encoder::new(SampleRate::Hz48000, Channels::Stereo, Application::Audio)
+    .expect("to create an encoder");
+
+  let pcm = run(Stream::new(Telemetry::new()), consume_pcm(&encoder));
+  let opus = run(Stream::new_framed(Telemetry::new()), consume_opus);
+
+  let realtime = Duration::from_secs(DURATION_SECS as u64);
+
+  for (name, elapsed) in [("pcm", pcm), ("opus", opus)] {
+    println!(
+      "{name:>4}: {:>8.2?} for {DURATION_SECS}s of audio, {:.2}% of a core per session",
+      elapsed,
+      elapsed.as_secs_f64() / realtime.as_secs_f64() * 100.0
+    );
+  }
+}
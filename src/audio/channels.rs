@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+
+/// How the left and right channels are mixed, for listeners who only hear one side well
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChannelMix {
+  /// Play the same audio on both channels
+  pub mono: bool,
+
+  /// From -100 (left only) through 0 (centered) to 100 (right only)
+  pub balance: i8,
+}
+
+impl ChannelMix {
+  /// Whether the audio passes through unchanged
+  pub fn is_neutral(&self) -> bool {
+    !self.mono && self.balance == 0
+  }
+
+  /// Pack the mix into a single value, so it can be shared through an atomic
+  pub(super) fn to_bits(self) -> u16 {
+    ((self.mono as u16) << 8) | self.balance as u8 as u16
+  }
+
+  pub(super) fn from_bits(bits: u16) -> Self {
+    Self {
+      mono: bits >> 8 != 0,
+      balance: (bits as u8 as i8).clamp(-100, 100),
+    }
+  }
+
+  /// Apply the mix to interleaved stereo samples
+  pub fn process(&self, samples: &mut [f32]) {
+    if self.is_neutral() {
+      return;
+    }
+
+    // Turning the balance to one side only attenuates the other side
+    let balance = self.balance as f32 / 100.0;
+    let left_gain = f32::min(1.0, 1.0 - balance);
+    let right_gain = f32::min(1.0, 1.0 + balance);
+
+    for frame in samples.chunks_exact_mut(2) {
+      if self.mono {
+        let mixed = (frame[0] + frame[1]) / 2.0;
+
+        frame[0] = mixed;
+        frame[1] = mixed;
+      }
+
+      frame[0] *= left_gain;
+      frame[1] *= right_gain;
+    }
+  }
+
+  /// A human readable description of the mix
+  pub fn describe(&self) -> String {
+    let balance = match self.balance {
+      0 => "Centered".to_string(),
+      balance if balance < 0 => format!("{}% left", -(balance as i16)),
+      balance => format!("{balance}% right"),
+    };
+
+    format!(
+      "Mono: **{}**\nBalance: **{}**",
+      if self.mono { "on" } else { "off" },
+      balance
+    )
+  }
+}
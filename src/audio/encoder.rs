@@ -0,0 +1,76 @@
+use audiopus::{coder::Encoder, Application, Bitrate, Channels, SampleRate};
+
+/// The amount of interleaved stereo samples in a single 20ms Opus frame at 48 kHz
+pub const FRAME_SAMPLES: usize = 960 * 2;
+
+/// The largest Opus packet the encoder is allowed to produce
+const MAX_PACKET_SIZE: usize = 1275;
+
+/// The largest DCA frame, a two byte length followed by the largest packet
+pub const MAX_FRAME_SIZE: usize = 2 + MAX_PACKET_SIZE;
+
+/// The bitrate of the encoded audio, matching what songbird uses for its own encoder
+const BITRATE: i32 = 128_000;
+
+/// An Opus "silence" frame, written whenever the stream runs dry
+pub const SILENCE_FRAME: [u8; 3] = [0xF8, 0xFF, 0xFE];
+
+/// Encodes 48 kHz stereo PCM into DCA framed Opus packets
+///
+/// Every packet is prefixed with its length as a little-endian `i16`,
+/// which is the framing songbird expects from a `Container::Dca` input.
+pub struct OpusEncoder {
+  encoder: Encoder,
+
+  /// Samples that did not fill up a complete frame yet
+  remainder: Vec<f32>,
+  packet: [u8; MAX_PACKET_SIZE],
+}
+
+impl OpusEncoder {
+  pub fn new() -> Result<Self, audiopus::Error> {
+    let mut encoder = Encoder::new(SampleRate::Hz48000, Channels::Stereo, Application::Audio)?;
+    encoder.set_bitrate(Bitrate::BitsPerSecond(BITRATE))?;
+
+    Ok(Self {
+      encoder,
+      remainder: Vec::with_capacity(FRAME_SAMPLES),
+      packet: [0; MAX_PACKET_SIZE],
+    })
+  }
+
+  /// Encode as many complete frames as possible, returning the DCA framed output
+  pub fn encode(&mut self, samples: &[f32]) -> Result<Vec<u8>, audiopus::Error> {
+    let mut output = Vec::new();
+
+    self.remainder.extend_from_slice(samples);
+
+    let frames = self.remainder.len() / FRAME_SAMPLES;
+
+    for frame in self.remainder[..frames * FRAME_SAMPLES].chunks_exact(FRAME_SAMPLES) {
+      let size = self.encoder.encode_float(frame, &mut self.packet)?;
+
+      output.extend_from_slice(&(size as i16).to_le_bytes());
+      output.extend_from_slice(&self.packet[..size]);
+    }
+
+    self.remainder.drain(..frames * FRAME_SAMPLES);
+
+    Ok(output)
+  }
+
+  /// Encode the samples that did not fill up a complete frame yet, padded with silence
+  pub fn finish(&mut self) -> Result<Vec<u8>, audiopus::Error> {
+    if self.remainder.is_empty() {
+      return Ok(Vec::new());
+    }
+
+    let padding = FRAME_SAMPLES - self.remainder.len();
+    self.encode(&vec![0.0; padding])
+  }
+
+  /// Discard any samples that have not been encoded yet
+  pub fn clear(&mut self) {
+    self.remainder.clear();
+  }
+}
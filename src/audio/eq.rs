@@ -0,0 +1,242 @@
+use std::{f32::consts::TAU, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use super::resampler::TARGET_SAMPLE_RATE;
+
+/// The equalizer presets a guild can choose from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EqPreset {
+  #[default]
+  Flat,
+  BassBoost,
+  Vocal,
+  Podcast,
+}
+
+impl EqPreset {
+  pub const ALL: [EqPreset; 4] = [Self::Flat, Self::BassBoost, Self::Vocal, Self::Podcast];
+
+  /// The identifier used in commands and the database
+  pub fn id(&self) -> &'static str {
+    match self {
+      Self::Flat => "flat",
+      Self::BassBoost => "bass_boost",
+      Self::Vocal => "vocal",
+      Self::Podcast => "podcast",
+    }
+  }
+
+  /// The human readable name of the preset
+  pub fn name(&self) -> &'static str {
+    match self {
+      Self::Flat => "Flat",
+      Self::BassBoost => "Bass boost",
+      Self::Vocal => "Vocal",
+      Self::Podcast => "Podcast",
+    }
+  }
+
+  /// The gain (in dB) applied before the filters, leaving headroom for boosted bands
+  fn preamp_db(&self) -> f32 {
+    match self {
+      Self::Flat => 0.0,
+      Self::BassBoost => -4.0,
+      Self::Vocal => -3.0,
+      Self::Podcast => -2.0,
+    }
+  }
+
+  fn bands(&self) -> &'static [Band] {
+    match self {
+      Self::Flat => &[],
+      Self::BassBoost => &[
+        Band::LowShelf {
+          freq: 120.0,
+          gain_db: 6.0,
+        },
+        Band::Peaking {
+          freq: 60.0,
+          gain_db: 2.0,
+          q: 1.0,
+        },
+      ],
+      Self::Vocal => &[
+        Band::LowShelf {
+          freq: 150.0,
+          gain_db: -3.0,
+        },
+        Band::Peaking {
+          freq: 2500.0,
+          gain_db: 4.0,
+          q: 1.0,
+        },
+      ],
+      Self::Podcast => &[
+        Band::HighPass { freq: 80.0, q: 0.7 },
+        Band::Peaking {
+          freq: 3000.0,
+          gain_db: 3.0,
+          q: 1.0,
+        },
+        Band::HighShelf {
+          freq: 8000.0,
+          gain_db: -3.0,
+        },
+      ],
+    }
+  }
+
+  pub(super) fn from_u8(value: u8) -> Self {
+    Self::ALL.get(value as usize).copied().unwrap_or_default()
+  }
+
+  pub(super) fn as_u8(&self) -> u8 {
+    *self as u8
+  }
+}
+
+impl FromStr for EqPreset {
+  type Err = ();
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Self::ALL
+      .into_iter()
+      .find(|preset| preset.id() == s)
+      .ok_or(())
+  }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Band {
+  LowShelf { freq: f32, gain_db: f32 },
+  HighShelf { freq: f32, gain_db: f32 },
+  Peaking { freq: f32, gain_db: f32, q: f32 },
+  HighPass { freq: f32, q: f32 },
+}
+
+/// A second order IIR filter, using the coefficients from the Audio EQ Cookbook
+struct Biquad {
+  b0: f32,
+  b1: f32,
+  b2: f32,
+  a1: f32,
+  a2: f32,
+
+  /// The previous two inputs and outputs, per channel
+  state: [[f32; 4]; 2],
+}
+
+impl Biquad {
+  fn new(band: Band) -> Self {
+    // Shelves use a slope of 1, which corresponds to this Q
+    const SHELF_Q: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+    let (freq, gain_db, q) = match band {
+      Band::LowShelf { freq, gain_db } | Band::HighShelf { freq, gain_db } => {
+        (freq, gain_db, SHELF_Q)
+      }
+      Band::Peaking { freq, gain_db, q } => (freq, gain_db, q),
+      Band::HighPass { freq, q } => (freq, 0.0, q),
+    };
+
+    let a = 10f32.powf(gain_db / 40.0);
+    let w0 = TAU * freq / TARGET_SAMPLE_RATE as f32;
+    let (sin, cos) = w0.sin_cos();
+    let alpha = sin / (2.0 * q);
+    let shelf = 2.0 * a.sqrt() * alpha;
+
+    let (b0, b1, b2, a0, a1, a2) = match band {
+      Band::LowShelf { .. } => (
+        a * ((a + 1.0) - (a - 1.0) * cos + shelf),
+        2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+        a * ((a + 1.0) - (a - 1.0) * cos - shelf),
+        (a + 1.0) + (a - 1.0) * cos + shelf,
+        -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+        (a + 1.0) + (a - 1.0) * cos - shelf,
+      ),
+      Band::HighShelf { .. } => (
+        a * ((a + 1.0) + (a - 1.0) * cos + shelf),
+        -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+        a * ((a + 1.0) + (a - 1.0) * cos - shelf),
+        (a + 1.0) - (a - 1.0) * cos + shelf,
+        2.0 * ((a - 1.0) - (a + 1.0) * cos),
+        (a + 1.0) - (a - 1.0) * cos - shelf,
+      ),
+      Band::Peaking { .. } => (
+        1.0 + alpha * a,
+        -2.0 * cos,
+        1.0 - alpha * a,
+        1.0 + alpha / a,
+        -2.0 * cos,
+        1.0 - alpha / a,
+      ),
+      Band::HighPass { .. } => (
+        (1.0 + cos) / 2.0,
+        -(1.0 + cos),
+        (1.0 + cos) / 2.0,
+        1.0 + alpha,
+        -2.0 * cos,
+        1.0 - alpha,
+      ),
+    };
+
+    Self {
+      b0: b0 / a0,
+      b1: b1 / a0,
+      b2: b2 / a0,
+      a1: a1 / a0,
+      a2: a2 / a0,
+      state: [[0.0; 4]; 2],
+    }
+  }
+
+  fn process(&mut self, channel: usize, input: f32) -> f32 {
+    let [x1, x2, y1, y2] = self.state[channel];
+    let output = self.b0 * input + self.b1 * x1 + self.b2 * x2 - self.a1 * y1 - self.a2 * y2;
+
+    self.state[channel] = [input, x1, output, y1];
+
+    output
+  }
+}
+
+/// Applies an equalizer preset to interleaved 48 kHz stereo samples
+pub struct Equalizer {
+  preset: EqPreset,
+  preamp: f32,
+  filters: Vec<Biquad>,
+}
+
+impl Equalizer {
+  pub fn new(preset: EqPreset) -> Self {
+    Self {
+      preset,
+      preamp: 10f32.powf(preset.preamp_db() / 20.0),
+      filters: preset.bands().iter().copied().map(Biquad::new).collect(),
+    }
+  }
+
+  pub fn preset(&self) -> EqPreset {
+    self.preset
+  }
+
+  pub fn process(&mut self, samples: &mut [f32]) {
+    if self.filters.is_empty() {
+      return;
+    }
+
+    for frame in samples.chunks_exact_mut(2) {
+      for (channel, sample) in frame.iter_mut().enumerate() {
+        let mut value = *sample * self.preamp;
+
+        for filter in &mut self.filters {
+          value = filter.process(channel, value);
+        }
+
+        *sample = value;
+      }
+    }
+  }
+}
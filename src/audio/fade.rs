@@ -0,0 +1,183 @@
+use std::{f32::consts::FRAC_PI_2, time::Duration};
+
+use super::resampler::TARGET_SAMPLE_RATE;
+
+/// The default length of the fade applied when playback starts or stops
+const DEFAULT_FADE: Duration = Duration::from_millis(15);
+
+/// The longest crossfade allowed, as the whole crossfade is held back before it is played
+const MAX_CROSSFADE: Duration = Duration::from_secs(3);
+
+/// The lengths of the fades applied by the sink
+#[derive(Clone, Copy, Debug)]
+pub struct FadeConfig {
+  /// The fade in/out applied when playback starts or stops
+  pub fade: Duration,
+
+  /// The overlap between consecutive tracks, zero disables crossfading
+  pub crossfade: Duration,
+}
+
+impl FadeConfig {
+  /// Read the lengths from the `FADE_DURATION` and `CROSSFADE_DURATION` environment variables
+  pub fn from_env() -> Self {
+    let read = |key: &str, default: Duration| match std::env::var(key) {
+      Ok(value) => value
+        .parse()
+        .map(Duration::from_millis)
+        .unwrap_or_else(|_| {
+          log::warn!("Invalid value for {key}: '{value}', falling back to {default:?}");
+          default
+        }),
+      Err(_) => default,
+    };
+
+    Self {
+      fade: read("FADE_DURATION", DEFAULT_FADE),
+      crossfade: read("CROSSFADE_DURATION", Duration::ZERO).min(MAX_CROSSFADE),
+    }
+  }
+}
+
+/// Applies gain ramps to interleaved 48 kHz stereo samples when playback starts, stops, or moves
+/// on to the next track
+///
+/// The most recent audio is held back (for the longest of the fade and crossfade lengths), so that
+/// it can still be faded out when playback stops, or be mixed with the start of the next track.
+pub struct Fader {
+  /// The lengths of the fades, in frames
+  fade: usize,
+  crossfade: usize,
+
+  /// Processed audio that has not been released yet
+  held: Vec<f32>,
+
+  /// How far into the fade-in the output is, if it is fading in
+  fade_in: Option<usize>,
+
+  /// The end of the previous track, mixed into the start of the current one
+  outgoing: Vec<f32>,
+  outgoing_pos: usize,
+}
+
+impl Fader {
+  pub fn new(config: FadeConfig) -> Self {
+    let frames =
+      |duration: Duration| (duration.as_secs_f64() * TARGET_SAMPLE_RATE as f64).round() as usize;
+
+    Self {
+      fade: frames(config.fade),
+      crossfade: frames(config.crossfade),
+      held: Vec::new(),
+      fade_in: None,
+      outgoing: Vec::new(),
+      outgoing_pos: 0,
+    }
+  }
+
+  /// The amount of interleaved samples that are held back
+  fn hold(&self) -> usize {
+    usize::max(self.fade, self.crossfade) * 2
+  }
+
+  /// Fade in the audio that comes after this
+  pub fn start(&mut self) {
+    if self.fade > 0 {
+      self.fade_in = Some(0);
+    }
+  }
+
+  /// Mix the held back end of the current track into the start of the next one
+  pub fn crossfade(&mut self) {
+    if self.crossfade == 0 {
+      return;
+    }
+
+    let length = usize::min(self.held.len(), self.crossfade * 2);
+    let start = self.held.len() - length;
+
+    self.outgoing = self.held.split_off(start);
+    self.outgoing_pos = 0;
+  }
+
+  /// Apply the active fades, returning the audio that no longer has to be held back
+  pub fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+    let mut samples = samples.to_vec();
+
+    // Equal power crossfade, so the loudness doesn't dip halfway through
+    if self.outgoing_pos < self.outgoing.len() {
+      let frames = self.outgoing.len() / 2;
+
+      for frame in samples.chunks_exact_mut(2) {
+        if self.outgoing_pos >= self.outgoing.len() {
+          break;
+        }
+
+        let t = (self.outgoing_pos / 2) as f32 / frames as f32 * FRAC_PI_2;
+        let (gain_in, gain_out) = t.sin_cos();
+
+        for (channel, sample) in frame.iter_mut().enumerate() {
+          *sample = *sample * gain_in + self.outgoing[self.outgoing_pos + channel] * gain_out;
+        }
+
+        self.outgoing_pos += 2;
+      }
+
+      if self.outgoing_pos >= self.outgoing.len() {
+        self.outgoing.clear();
+        self.outgoing_pos = 0;
+      }
+    }
+
+    if let Some(position) = self.fade_in.as_mut() {
+      for frame in samples.chunks_exact_mut(2) {
+        if *position >= self.fade {
+          break;
+        }
+
+        let gain = ramp(*position, self.fade);
+        frame.iter_mut().for_each(|sample| *sample *= gain);
+
+        *position += 1;
+      }
+
+      if *position >= self.fade {
+        self.fade_in = None;
+      }
+    }
+
+    self.held.extend_from_slice(&samples);
+
+    let release = self.held.len().saturating_sub(self.hold());
+    self.held.drain(..release).collect()
+  }
+
+  /// Fade out and release the audio that directly follows what was already released
+  ///
+  /// Only the length of the fade is kept, so that pausing doesn't wait for what was held back for a
+  /// crossfade. The rest of it is discarded.
+  pub fn stop(&mut self) -> Vec<f32> {
+    let mut samples = std::mem::take(&mut self.held);
+    samples.truncate(self.fade * 2);
+
+    let frames = samples.len() / 2;
+
+    for (position, frame) in samples.chunks_exact_mut(2).enumerate() {
+      let gain = ramp(frames - position - 1, frames);
+      frame.iter_mut().for_each(|sample| *sample *= gain);
+    }
+
+    self.fade_in = None;
+    self.outgoing.clear();
+    self.outgoing_pos = 0;
+
+    samples
+  }
+}
+
+/// A raised cosine ramp from 0 to 1 over `length` frames
+fn ramp(position: usize, length: usize) -> f32 {
+  let t = position as f32 / length as f32;
+
+  0.5 - 0.5 * (t * std::f32::consts::PI).cos()
+}
@@ -1,73 +1,287 @@
+pub mod channels;
+pub mod encoder;
+pub mod eq;
+pub mod fade;
+pub mod resampler;
+pub mod settings;
 pub mod stream;
+pub mod stretch;
+pub mod telemetry;
 
-use self::stream::Stream;
+mod ring;
+
+#[cfg(test)]
+mod bench;
+#[cfg(test)]
+mod tests;
+
+use self::{
+  encoder::OpusEncoder,
+  eq::Equalizer,
+  fade::{FadeConfig, Fader},
+  resampler::{Resampler, ResamplerQuality},
+  settings::AudioSettings,
+  stream::Stream,
+  stretch::TimeStretcher,
+};
 
 use librespot::playback::audio_backend::{Sink, SinkAsBytes, SinkError, SinkResult};
 use librespot::playback::convert::Converter;
 use librespot::playback::decoder::AudioPacket;
 use log::error;
-use std::io::Write;
+use std::{io::Write, time::Instant};
 use tokio::sync::mpsc::UnboundedSender;
 
 pub enum SinkEvent {
   Start,
   Stop,
+
+  /// The sink ran into an error it cannot recover from, and no longer produces audio
+  Failed(String),
+}
+
+/// The format in which audio is handed over to songbird
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputMode {
+  /// Raw float PCM, which songbird encodes for every call
+  Pcm,
+
+  /// Opus frames encoded by the sink, which songbird can pass through as-is
+  Opus,
+}
+
+impl OutputMode {
+  /// Read the output mode from the `AUDIO_OUTPUT` environment variable, defaulting to `Pcm`
+  pub fn from_env() -> Self {
+    match std::env::var("AUDIO_OUTPUT") {
+      Ok(value) => match value.to_lowercase().as_str() {
+        "pcm" => Self::Pcm,
+        "opus" => Self::Opus,
+        _ => {
+          log::warn!("Unknown audio output '{value}', falling back to 'pcm'");
+          Self::Pcm
+        }
+      },
+      Err(_) => Self::Pcm,
+    }
+  }
 }
 
 pub struct StreamSink {
   stream: Stream,
   sender: UnboundedSender<SinkEvent>,
+  settings: AudioSettings,
+
+  quality: ResamplerQuality,
+  resampler: Option<Resampler>,
+  encoder: Option<OpusEncoder>,
+  stretcher: TimeStretcher,
+  equalizer: Equalizer,
+  fader: Fader,
+
+  /// The amount of track changes the fader has seen, see `AudioSettings::signal_track_change`
+  track_changes: u32,
+
+  /// Set after an unrecoverable error, after which all audio is discarded
+  failed: bool,
 }
 
 impl StreamSink {
-  pub fn new(stream: Stream, sender: UnboundedSender<SinkEvent>) -> Self {
-    Self { stream, sender }
+  pub fn new(stream: Stream, sender: UnboundedSender<SinkEvent>, settings: AudioSettings) -> Self {
+    let equalizer = Equalizer::new(settings.eq_preset());
+    let track_changes = settings.track_changes();
+
+    Self {
+      stream,
+      sender,
+      settings,
+      quality: ResamplerQuality::from_env(),
+      resampler: None,
+      encoder: None,
+      stretcher: TimeStretcher::new(),
+      equalizer,
+      fader: Fader::new(FadeConfig::from_env()),
+      track_changes,
+      failed: false,
+    }
+  }
+
+  /// Get the resampler, creating it if this is the first packet written to the sink
+  fn resampler(&mut self) -> SinkResult<&mut Resampler> {
+    if self.resampler.is_none() {
+      let resampler = Resampler::new(self.quality)
+        .map_err(|why| SinkError::InvalidParams(format!("Failed to create resampler: {why}")))?;
+
+      self.resampler = Some(resampler);
+    }
+
+    Ok(self.resampler.as_mut().expect("to contain a value"))
+  }
+
+  /// Get the Opus encoder, creating it if this is the first packet written to the sink
+  fn encoder(&mut self) -> SinkResult<&mut OpusEncoder> {
+    if self.encoder.is_none() {
+      let encoder = OpusEncoder::new()
+        .map_err(|why| SinkError::InvalidParams(format!("Failed to create encoder: {why}")))?;
+
+      self.encoder = Some(encoder);
+    }
+
+    Ok(self.encoder.as_mut().expect("to contain a value"))
+  }
+
+  /// Resample, stretch, equalize, mix and fade a packet, and write it to the stream
+  fn process(&mut self, samples: &[f64], converter: &mut Converter) -> SinkResult<()> {
+    let samples_f32: &[f32] = &converter.f64_to_f32(samples);
+
+    let resample_start = Instant::now();
+    let resampled = self
+      .resampler()?
+      .process(samples_f32)
+      .map_err(|why| SinkError::OnWrite(format!("Failed to resample audio: {why}")))?;
+
+    self
+      .stream
+      .telemetry()
+      .record_resample(resample_start.elapsed());
+
+    // Switching back to normal speed releases the audio the stretcher was still holding on to
+    let speed = self.settings.playback_speed();
+    let mut samples = self.stretcher.set_speed(speed as f64);
+    samples.extend(self.stretcher.process(&resampled));
+
+    // Pick up preset changes made since the last packet
+    let preset = self.settings.eq_preset();
+    if self.equalizer.preset() != preset {
+      self.equalizer = Equalizer::new(preset);
+    }
+
+    self.equalizer.process(&mut samples);
+    self.settings.channel_mix().process(&mut samples);
+
+    // The player moved on to another track since the last packet
+    let track_changes = self.settings.track_changes();
+    if self.track_changes != track_changes {
+      self.track_changes = track_changes;
+      self.fader.crossfade();
+    }
+
+    let samples = self.fader.process(&samples);
+    self.write_samples(&samples, false)
+  }
+
+  /// Write samples to the stream, encoding them first if the stream carries Opus
+  ///
+  /// With `last` set, samples that do not fill up a complete Opus frame are padded with silence.
+  fn write_samples(&mut self, samples: &[f32], last: bool) -> SinkResult<()> {
+    use zerocopy::AsBytes;
+
+    if self.stream.is_framed() {
+      let encoder = self.encoder()?;
+      let mut frames = encoder
+        .encode(samples)
+        .map_err(|why| SinkError::OnWrite(format!("Failed to encode audio: {why}")))?;
+
+      if last {
+        frames.extend(
+          encoder
+            .finish()
+            .map_err(|why| SinkError::OnWrite(format!("Failed to encode audio: {why}")))?,
+        );
+      }
+
+      if !frames.is_empty() {
+        self.write_bytes(&frames)?;
+      }
+    } else if !samples.is_empty() {
+      self.write_bytes(samples.as_bytes())?;
+    }
+
+    self.stream.telemetry().record_write(samples.len());
+
+    Ok(())
+  }
+
+  /// Stop producing audio and let the player know, so that only this session is torn down
+  fn fail(&mut self, reason: String) {
+    if self.failed {
+      return;
+    }
+
+    error!("Audio sink failed: {reason}");
+
+    self.failed = true;
+    self.stream.flush().ok();
+
+    // If the player is already gone there is nobody left to tell
+    self.sender.send(SinkEvent::Failed(reason)).ok();
   }
 }
 
 impl Sink for StreamSink {
+  // librespot-playback exits the process with status 1 when a sink returns an error, which would
+  // take down every session. Failures are therefore reported to the player instead.
+
   fn start(&mut self) -> SinkResult<()> {
-    if let Err(why) = self.sender.send(SinkEvent::Start) {
-      // WARNING: Returning an error causes librespot-playback to exit the process with status 1
+    self.stream.telemetry().record_start();
+    self.fader.start();
 
-      error!("Failed to send start playback event: {why}");
-      return Err(SinkError::ConnectionRefused(why.to_string()));
+    if let Err(why) = self.sender.send(SinkEvent::Start) {
+      self.fail(format!("Failed to send start playback event: {why}"));
     }
 
     Ok(())
   }
 
   fn stop(&mut self) -> SinkResult<()> {
+    self.stream.telemetry().record_stop();
+
     if let Err(why) = self.sender.send(SinkEvent::Stop) {
-      // WARNING: Returning an error causes librespot-playback to exit the process with status 1
+      self.fail(format!("Failed to send stop playback event: {why}"));
+    }
+
+    // librespot also stops the sink when pausing, so this covers pausing as well
+    let tail = self.fader.stop();
 
-      error!("Failed to send start playback event: {why}");
-      return Err(SinkError::ConnectionRefused(why.to_string()));
+    if tail.is_empty() || self.failed {
+      self.stream.flush().ok();
+    } else {
+      // Let the buffered audio play out and end it with the faded tail, instead of cutting it off
+      if let Err(why) = self.write_samples(&tail, true) {
+        self.fail(why.to_string());
+      }
+
+      self.stream.mark_idle();
     }
 
-    self.stream.flush().ok();
+    // The output was interrupted, so the filter state no longer matches the next packet
+    if let Some(resampler) = self.resampler.as_mut() {
+      if let Err(why) = resampler.reset() {
+        error!("Failed to reset resampler: {why}");
+      }
+    }
+
+    if let Some(encoder) = self.encoder.as_mut() {
+      encoder.clear();
+    }
+
+    self.stretcher.reset();
 
     Ok(())
   }
 
   fn write(&mut self, packet: AudioPacket, converter: &mut Converter) -> SinkResult<()> {
-    use zerocopy::AsBytes;
+    if self.failed {
+      return Ok(());
+    }
 
     let AudioPacket::Samples(samples) = packet else {
       return Ok(());
     };
-    let samples_f32: &[f32] = &converter.f64_to_f32(&samples);
-
-    let resampled = samplerate::convert(
-      44100,
-      48000,
-      2,
-      samplerate::ConverterType::Linear,
-      samples_f32,
-    )
-    .expect("to succeed");
-
-    self.write_bytes(resampled.as_bytes())?;
+
+    if let Err(why) = self.process(&samples, converter) {
+      self.fail(why.to_string());
+    }
 
     Ok(())
   }
@@ -0,0 +1,72 @@
+use librespot::playback::{NUM_CHANNELS, SAMPLE_RATE};
+use samplerate::{ConverterType, Samplerate};
+
+/// The sample rate Discord (and songbird) expects
+pub const TARGET_SAMPLE_RATE: u32 = 48000;
+
+/// The quality of the sinc converter used to resample librespot's output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResamplerQuality {
+  Fast,
+  Medium,
+  Best,
+}
+
+impl ResamplerQuality {
+  /// Read the quality from the `RESAMPLER_QUALITY` environment variable, defaulting to `Fast`
+  pub fn from_env() -> Self {
+    match std::env::var("RESAMPLER_QUALITY") {
+      Ok(value) => match value.to_lowercase().as_str() {
+        "fast" => Self::Fast,
+        "medium" => Self::Medium,
+        "best" => Self::Best,
+        _ => {
+          log::warn!("Unknown resampler quality '{value}', falling back to 'fast'");
+          Self::Fast
+        }
+      },
+      Err(_) => Self::Fast,
+    }
+  }
+}
+
+impl From<ResamplerQuality> for ConverterType {
+  fn from(value: ResamplerQuality) -> Self {
+    match value {
+      ResamplerQuality::Fast => ConverterType::SincFastest,
+      ResamplerQuality::Medium => ConverterType::SincMediumQuality,
+      ResamplerQuality::Best => ConverterType::SincBestQuality,
+    }
+  }
+}
+
+/// A streaming resampler that converts librespot's 44.1 kHz output to 48 kHz
+///
+/// Unlike `samplerate::convert`, the filter state is carried over between calls,
+/// so consecutive packets are resampled without discontinuities at their boundaries.
+pub struct Resampler {
+  converter: Samplerate,
+}
+
+impl Resampler {
+  pub fn new(quality: ResamplerQuality) -> Result<Self, samplerate::Error> {
+    let converter = Samplerate::new(
+      quality.into(),
+      SAMPLE_RATE,
+      TARGET_SAMPLE_RATE,
+      NUM_CHANNELS as usize,
+    )?;
+
+    Ok(Self { converter })
+  }
+
+  /// Resample a block of interleaved stereo samples
+  pub fn process(&mut self, samples: &[f32]) -> Result<Vec<f32>, samplerate::Error> {
+    self.converter.process(samples)
+  }
+
+  /// Discard the filter state, used when the output is interrupted (e.g. pausing or seeking)
+  pub fn reset(&mut self) -> Result<(), samplerate::Error> {
+    self.converter.reset()
+  }
+}
@@ -0,0 +1,127 @@
+use std::{
+  cell::UnsafeCell,
+  sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// A fixed-capacity, lock-free, single-producer single-consumer byte ring buffer
+///
+/// `push` may only be called from one thread (the producer), and `peek`, `pop` and `skip_to` may
+/// only be called from one thread (the consumer). All other methods can be called from anywhere.
+pub struct RingBuffer {
+  buffer: Box<[UnsafeCell<u8>]>,
+
+  /// The total amount of bytes ever consumed, only advanced by the consumer
+  head: AtomicUsize,
+
+  /// The total amount of bytes ever produced, only advanced by the producer
+  tail: AtomicUsize,
+}
+
+// SAFETY: The producer only writes to the free region between `tail` and `head + capacity`,
+// the consumer only reads from the region between `head` and `tail`. These regions never overlap,
+// and are only handed over to the other side after the copy has completed.
+unsafe impl Sync for RingBuffer {}
+
+impl RingBuffer {
+  pub fn new(capacity: usize) -> Self {
+    Self {
+      buffer: (0..capacity).map(|_| UnsafeCell::new(0)).collect(),
+      head: AtomicUsize::new(0),
+      tail: AtomicUsize::new(0),
+    }
+  }
+
+  pub fn capacity(&self) -> usize {
+    self.buffer.len()
+  }
+
+  /// The amount of bytes that can currently be read
+  pub fn len(&self) -> usize {
+    let tail = self.tail.load(Ordering::Acquire);
+    let head = self.head.load(Ordering::Acquire);
+
+    tail.wrapping_sub(head)
+  }
+
+  /// The amount of bytes that can currently be written
+  pub fn free(&self) -> usize {
+    self.capacity() - self.len()
+  }
+
+  /// The write position, which can be passed to `skip_to` to discard everything written so far
+  pub fn tail(&self) -> usize {
+    self.tail.load(Ordering::Acquire)
+  }
+
+  /// Write as many bytes as fit, returning the amount of bytes written (producer only)
+  pub fn push(&self, data: &[u8]) -> usize {
+    let tail = self.tail.load(Ordering::Relaxed);
+    let amount = usize::min(data.len(), self.free());
+
+    self.copy_in(tail, &data[..amount]);
+    self
+      .tail
+      .store(tail.wrapping_add(amount), Ordering::Release);
+
+    amount
+  }
+
+  /// Copy bytes into `buf` without consuming them, returning the amount of bytes copied (consumer only)
+  pub fn peek(&self, buf: &mut [u8]) -> usize {
+    let head = self.head.load(Ordering::Relaxed);
+    let amount = usize::min(buf.len(), self.len());
+
+    self.copy_out(head, &mut buf[..amount]);
+
+    amount
+  }
+
+  /// Move bytes into `buf`, returning the amount of bytes read (consumer only)
+  pub fn pop(&self, buf: &mut [u8]) -> usize {
+    let amount = self.peek(buf);
+    let head = self.head.load(Ordering::Relaxed);
+
+    self
+      .head
+      .store(head.wrapping_add(amount), Ordering::Release);
+
+    amount
+  }
+
+  /// Discard everything before `position`, if it has not been read yet (consumer only)
+  pub fn skip_to(&self, position: usize) {
+    let head = self.head.load(Ordering::Relaxed);
+
+    // A position behind the read position wraps around to a value larger than the length
+    if position.wrapping_sub(head) <= self.len() {
+      self.head.store(position, Ordering::Release);
+    }
+  }
+
+  fn copy_in(&self, position: usize, data: &[u8]) {
+    let start = position % self.capacity();
+    let first = usize::min(data.len(), self.capacity() - start);
+
+    // SAFETY: See the `Sync` implementation, the target region is owned by the producer
+    unsafe {
+      std::ptr::copy_nonoverlapping(data.as_ptr(), self.ptr(start), first);
+      std::ptr::copy_nonoverlapping(data[first..].as_ptr(), self.ptr(0), data.len() - first);
+    }
+  }
+
+  fn copy_out(&self, position: usize, buf: &mut [u8]) {
+    let start = position % self.capacity();
+    let first = usize::min(buf.len(), self.capacity() - start);
+    let rest = buf.len() - first;
+
+    // SAFETY: See the `Sync` implementation, the source region is owned by the consumer
+    unsafe {
+      std::ptr::copy_nonoverlapping(self.ptr(start), buf.as_mut_ptr(), first);
+      std::ptr::copy_nonoverlapping(self.ptr(0), buf[first..].as_mut_ptr(), rest);
+    }
+  }
+
+  fn ptr(&self, index: usize) -> *mut u8 {
+    UnsafeCell::raw_get(self.buffer[index..].as_ptr())
+  }
+}
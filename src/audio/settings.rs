@@ -0,0 +1,95 @@
+use std::sync::{
+  atomic::{AtomicBool, AtomicU16, AtomicU32, AtomicU8, Ordering},
+  Arc,
+};
+
+use super::{channels::ChannelMix, eq::EqPreset};
+
+/// Audio settings shared between a session and its sink, which can be changed during playback
+#[derive(Clone, Default)]
+pub struct AudioSettings(Arc<InnerAudioSettings>);
+
+struct InnerAudioSettings {
+  eq_preset: AtomicU8,
+  channel_mix: AtomicU16,
+
+  /// Incremented whenever the player moves on to another track
+  track_changes: AtomicU32,
+
+  /// The playback speed for episodes, stored as the bits of an `f32`
+  speed: AtomicU32,
+
+  /// Whether the player is currently playing a podcast episode
+  episode: AtomicBool,
+}
+
+impl Default for InnerAudioSettings {
+  fn default() -> Self {
+    Self {
+      eq_preset: AtomicU8::default(),
+      channel_mix: AtomicU16::default(),
+      track_changes: AtomicU32::default(),
+      speed: AtomicU32::new(1.0f32.to_bits()),
+      episode: AtomicBool::default(),
+    }
+  }
+}
+
+impl AudioSettings {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Get the active equalizer preset
+  pub fn eq_preset(&self) -> EqPreset {
+    EqPreset::from_u8(self.0.eq_preset.load(Ordering::Relaxed))
+  }
+
+  /// Change the equalizer preset, which the sink picks up on the next packet
+  pub fn set_eq_preset(&self, preset: EqPreset) {
+    self.0.eq_preset.store(preset.as_u8(), Ordering::Relaxed);
+  }
+
+  /// Get the active channel mix
+  pub fn channel_mix(&self) -> ChannelMix {
+    ChannelMix::from_bits(self.0.channel_mix.load(Ordering::Relaxed))
+  }
+
+  /// Change the channel mix, which the sink picks up on the next packet
+  pub fn set_channel_mix(&self, mix: ChannelMix) {
+    self.0.channel_mix.store(mix.to_bits(), Ordering::Relaxed);
+  }
+
+  /// Let the sink know that the player moved on to another track, so it can crossfade
+  pub fn signal_track_change(&self) {
+    self.0.track_changes.fetch_add(1, Ordering::Relaxed);
+  }
+
+  pub(super) fn track_changes(&self) -> u32 {
+    self.0.track_changes.load(Ordering::Relaxed)
+  }
+
+  /// Get the playback speed used for episodes
+  pub fn speed(&self) -> f32 {
+    f32::from_bits(self.0.speed.load(Ordering::Relaxed))
+  }
+
+  /// Change the playback speed used for episodes, which the sink picks up on the next packet
+  pub fn set_speed(&self, speed: f32) {
+    self.0.speed.store(speed.to_bits(), Ordering::Relaxed);
+  }
+
+  /// Let the sink know whether the player is playing an episode, as only those are sped up
+  pub fn set_episode(&self, episode: bool) {
+    self.0.episode.store(episode, Ordering::Relaxed);
+  }
+
+  /// The speed the sink should currently play at
+  pub(super) fn playback_speed(&self) -> f32 {
+    if self.0.episode.load(Ordering::Relaxed) {
+      self.speed()
+    } else {
+      1.0
+    }
+  }
+}
@@ -1,73 +1,474 @@
 use std::{
   io::{Read, Seek, Write},
-  sync::{Arc, Condvar, Mutex},
+  sync::{
+    atomic::{fence, AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    Arc, Mutex,
+  },
+  thread::Thread,
+  time::{Duration, Instant},
 };
 
 use songbird::input::reader::MediaSource;
 
+use super::{
+  encoder::{MAX_FRAME_SIZE, SILENCE_FRAME},
+  ring::RingBuffer,
+  telemetry::Telemetry,
+};
+
+/// The amount of bytes per millisecond of 48 kHz stereo float PCM
+const PCM_BYTES_PER_MS: usize = 48 * 2 * 4;
+
+/// The (approximate) amount of bytes per millisecond of DCA framed Opus at 128 kbps
+const FRAMED_BYTES_PER_MS: usize = 17;
+
+/// The default size the buffer starts out with, in milliseconds
+const DEFAULT_TARGET_MS: usize = 85;
+
 /// The lower the value, the less latency
 ///
 /// Too low of a value results in unpredictable audio
-const MAX_SIZE: usize = 32 * 1024;
+const DEFAULT_FLOOR_MS: usize = 40;
+
+/// The upper limit the buffer can grow to on bad connections, in milliseconds
+const DEFAULT_CEILING_MS: usize = 500;
+
+/// The amount of separate starvations after which the buffer grows
+const GROW_AFTER: u32 = 3;
+
+/// How long the stream must go without starvation before the buffer shrinks
+const STABLE_PERIOD: Duration = Duration::from_secs(30);
+
+/// How long a blocked writer sleeps before checking for space again, in case a wakeup was missed
+const PARK_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Counters describing how well the writer and reader of a stream keep up with each other
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StreamStats {
+  /// The amount of reads that had to be filled with silence while audio was expected
+  pub underruns: u64,
+
+  /// The amount of writes that had to wait for the reader to make room
+  pub blocked_writes: u64,
+
+  /// The current target size of the buffer, in milliseconds
+  pub target_ms: usize,
+}
+
+/// The bounds between which the buffer size of a stream adapts, in milliseconds
+#[derive(Clone, Copy, Debug)]
+pub struct JitterBounds {
+  pub floor_ms: usize,
+  pub ceiling_ms: usize,
+}
+
+impl JitterBounds {
+  /// Read the bounds from the `JITTER_BUFFER_MIN` and `JITTER_BUFFER_MAX` environment variables
+  pub fn from_env() -> Self {
+    let read = |key: &str, default: usize| match std::env::var(key) {
+      Ok(value) => value.parse().unwrap_or_else(|_| {
+        log::warn!("Invalid value for {key}: '{value}', falling back to {default}");
+        default
+      }),
+      Err(_) => default,
+    };
+
+    let floor_ms = read("JITTER_BUFFER_MIN", DEFAULT_FLOOR_MS).max(1);
+    let ceiling_ms = read("JITTER_BUFFER_MAX", DEFAULT_CEILING_MS).max(floor_ms);
 
+    Self {
+      floor_ms,
+      ceiling_ms,
+    }
+  }
+}
+
+/// Reader-side bookkeeping used to adapt the buffer size to the observed starvation
+#[derive(Clone)]
+struct Jitter {
+  /// Whether the previous read was an underrun, so one gap only counts as a single starvation
+  starving: bool,
+  starvations: u32,
+
+  /// The last time the buffer starved or was resized
+  last_change: Instant,
+}
+
+impl Jitter {
+  fn new() -> Self {
+    Self {
+      starving: false,
+      starvations: 0,
+      last_change: Instant::now(),
+    }
+  }
+}
+
+struct Shared {
+  ring: RingBuffer,
+
+  /// The amount of bytes the writer may fill the buffer up to, adapted by the reader
+  target: AtomicUsize,
+  floor: usize,
+  bytes_per_ms: usize,
+
+  /// The position up to which the reader must discard data, set by `flush`
+  flush_to: AtomicUsize,
+
+  /// Incremented on every flush, so that a blocked writer knows its data is no longer wanted
+  flushes: AtomicUsize,
+
+  /// Whether audio has been written since the last flush
+  ///
+  /// Empty reads are only counted as underruns while this is set, so silence while paused isn't
+  primed: AtomicBool,
+
+  /// The writer thread, if it is waiting for space. Only locked when the buffer is full.
+  writer: Mutex<Option<Thread>>,
+  writer_waiting: AtomicBool,
+
+  underruns: AtomicU64,
+  blocked_writes: AtomicU64,
+
+  telemetry: Telemetry,
+}
+
+impl Shared {
+  /// Discard everything that was written before the last flush
+  fn apply_flush(&self) {
+    self.ring.skip_to(self.flush_to.load(Ordering::Acquire));
+  }
+
+  /// Wake up the writer if it is waiting for space
+  fn wake_writer(&self) {
+    fence(Ordering::SeqCst);
+
+    if self.writer_waiting.swap(false, Ordering::SeqCst) {
+      if let Some(writer) = self.writer.lock().expect("Mutex was poisoned").as_ref() {
+        writer.unpark();
+      }
+    }
+  }
+
+  /// Record an empty read, returning whether audio was expected
+  fn underrun(&self) -> bool {
+    if !self.primed.load(Ordering::Relaxed) {
+      return false;
+    }
+
+    self.underruns.fetch_add(1, Ordering::Relaxed);
+
+    true
+  }
+
+  fn resize(&self, target: usize) {
+    let target = target.clamp(self.floor, self.ring.capacity());
+    let previous = self.target.swap(target, Ordering::Relaxed);
+
+    self.telemetry.set_buffer_target(target / self.bytes_per_ms);
+
+    if previous != target {
+      log::debug!(
+        "Resized stream buffer from {}ms to {}ms",
+        previous / self.bytes_per_ms,
+        target / self.bytes_per_ms
+      );
+    }
+
+    // The writer might be waiting for room that is now available
+    self.wake_writer();
+  }
+}
+
+/// A single-producer single-consumer audio buffer between librespot and songbird
+///
+/// One clone is written to by the `StreamSink`, another is read from by songbird.
 #[derive(Clone)]
 pub struct Stream {
-  inner: Arc<(Mutex<Vec<u8>>, Condvar)>,
+  inner: Arc<Shared>,
+
+  /// Whether the stream contains DCA framed Opus packets instead of raw PCM
+  framed: bool,
+
+  /// The part of a frame that has not yet been read by the reader
+  ///
+  /// This is local to the reader, so that flushing the stream never splits up a frame
+  pending: Vec<u8>,
+
+  jitter: Jitter,
 }
 
 impl Stream {
-  pub fn new() -> Self {
+  pub fn new(telemetry: Telemetry) -> Self {
+    Self::with_bounds(JitterBounds::from_env(), false, telemetry)
+  }
+
+  /// Create a stream that carries DCA framed Opus packets
+  pub fn new_framed(telemetry: Telemetry) -> Self {
+    Self::with_bounds(JitterBounds::from_env(), true, telemetry)
+  }
+
+  pub(super) fn with_bounds(bounds: JitterBounds, framed: bool, telemetry: Telemetry) -> Self {
+    let bytes_per_ms = if framed {
+      FRAMED_BYTES_PER_MS
+    } else {
+      PCM_BYTES_PER_MS
+    };
+
+    let floor = bounds.floor_ms * bytes_per_ms;
+    let ceiling = if framed {
+      // A frame is never split, so the buffer must be able to hold the largest one
+      usize::max(bounds.ceiling_ms * bytes_per_ms, MAX_FRAME_SIZE)
+    } else {
+      bounds.ceiling_ms * bytes_per_ms
+    };
+    let target = (DEFAULT_TARGET_MS * bytes_per_ms).clamp(floor, ceiling);
+
+    telemetry.set_buffer_target(target / bytes_per_ms);
+
     Self {
-      inner: Arc::new((Mutex::new(Vec::new()), Condvar::new())),
+      inner: Arc::new(Shared {
+        ring: RingBuffer::new(ceiling),
+        target: AtomicUsize::new(target),
+        floor,
+        bytes_per_ms,
+        flush_to: AtomicUsize::new(0),
+        flushes: AtomicUsize::new(0),
+        primed: AtomicBool::new(false),
+        writer: Mutex::new(None),
+        writer_waiting: AtomicBool::new(false),
+        underruns: AtomicU64::new(0),
+        blocked_writes: AtomicU64::new(0),
+        telemetry,
+      }),
+      framed,
+      pending: Vec::new(),
+      jitter: Jitter::new(),
+    }
+  }
+
+  /// Whether the stream contains DCA framed Opus packets
+  pub fn is_framed(&self) -> bool {
+    self.framed
+  }
+
+  /// The amount of bytes currently waiting to be read, used by the tests and the bench
+  #[cfg(test)]
+  pub fn buffered(&self) -> usize {
+    self.inner.ring.len() + self.pending.len()
+  }
+
+  /// Get the underrun and blocked write counters of this stream
+  pub fn stats(&self) -> StreamStats {
+    StreamStats {
+      underruns: self.inner.underruns.load(Ordering::Relaxed),
+      blocked_writes: self.inner.blocked_writes.load(Ordering::Relaxed),
+      target_ms: self.inner.target.load(Ordering::Relaxed) / self.inner.bytes_per_ms,
+    }
+  }
+
+  /// Mark the end of the written audio, so the reader running dry afterwards isn't a starvation
+  pub fn mark_idle(&self) {
+    self.inner.primed.store(false, Ordering::Relaxed);
+  }
+
+  /// The telemetry counters this stream (and the sink writing to it) report to
+  pub fn telemetry(&self) -> &Telemetry {
+    &self.inner.telemetry
+  }
+
+  /// Called by the reader after an empty read, grows the buffer after repeated starvation
+  fn on_underrun(&mut self) {
+    if !self.inner.underrun() || self.jitter.starving {
+      return;
+    }
+
+    self.jitter.starving = true;
+    self.jitter.starvations += 1;
+    self.jitter.last_change = Instant::now();
+
+    if self.jitter.starvations >= GROW_AFTER {
+      self.jitter.starvations = 0;
+
+      let target = self.inner.target.load(Ordering::Relaxed);
+      self.inner.resize(target + target / 2);
+    }
+  }
+
+  /// Called by the reader after a successful read, shrinks the buffer once things are stable
+  fn on_read(&mut self) {
+    self.jitter.starving = false;
+
+    if self.jitter.last_change.elapsed() < STABLE_PERIOD {
+      return;
+    }
+
+    self.jitter.starvations = 0;
+    self.jitter.last_change = Instant::now();
+
+    let target = self.inner.target.load(Ordering::Relaxed);
+    if target > self.inner.floor {
+      self.inner.resize(target - target / 8);
+    }
+  }
+
+  /// Read the next frame from the buffer into `pending`, or a silence frame if the buffer is empty
+  fn read_frame(&mut self) {
+    let shared = &*self.inner;
+    shared.apply_flush();
+
+    let mut header = [0u8; 2];
+    let size = if shared.ring.peek(&mut header) == 2 {
+      2 + i16::from_le_bytes(header).max(0) as usize
+    } else {
+      usize::MAX
+    };
+
+    // Only take complete frames, the rest of the frame may still be on its way
+    if shared.ring.len() < size {
+      shared.telemetry.record_zero_fill();
+      self.on_underrun();
+
+      self
+        .pending
+        .extend_from_slice(&(SILENCE_FRAME.len() as i16).to_le_bytes());
+      self.pending.extend_from_slice(&SILENCE_FRAME);
+
+      return;
+    }
+
+    self.pending.resize(size, 0);
+    shared.ring.pop(&mut self.pending);
+    shared.telemetry.record_read(size);
+    shared.wake_writer();
+
+    self.on_read();
+  }
+
+  /// The length of the largest part of `buf` that can be written in one go
+  ///
+  /// For framed streams this only includes complete frames, so a frame is never split by a flush
+  fn write_len(&self, buf: &[u8]) -> usize {
+    let capacity = self.inner.ring.capacity();
+
+    if !self.framed {
+      return usize::min(buf.len(), capacity);
+    }
+
+    let mut length = 0;
+
+    while length + 2 <= buf.len() {
+      let size = 2 + i16::from_le_bytes([buf[length], buf[length + 1]]).max(0) as usize;
+
+      if length > 0 && length + size > capacity {
+        break;
+      }
+
+      length += size;
     }
+
+    usize::min(usize::max(length, 1), buf.len())
   }
 }
 
 impl Read for Stream {
   fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-    let (mutex, condvar) = &*self.inner;
-    let mut buffer = mutex.lock().expect("Mutex was poisoned");
+    if self.framed {
+      if self.pending.is_empty() {
+        self.read_frame();
+      }
+
+      let max_read = usize::min(buf.len(), self.pending.len());
+
+      buf[0..max_read].copy_from_slice(&self.pending[0..max_read]);
+      self.pending.drain(0..max_read);
+
+      return Ok(max_read);
+    }
+
+    let shared = &*self.inner;
+    shared.apply_flush();
+
+    let read = shared.ring.pop(buf);
 
     // Prevent Discord jitter by filling buffer with zeroes if we don't have any audio
     // (i.e. when you skip too far ahead in a song which hasn't been downloaded yet)
-    if buffer.is_empty() {
+    if read == 0 {
       buf.fill(0);
-      condvar.notify_all();
+      shared.telemetry.record_zero_fill();
+      self.on_underrun();
 
       return Ok(buf.len());
     }
 
-    let max_read = usize::min(buf.len(), buffer.len());
+    shared.telemetry.record_read(read);
+    shared.wake_writer();
+    self.on_read();
 
-    buf[0..max_read].copy_from_slice(&buffer[0..max_read]);
-    buffer.drain(0..max_read);
-    condvar.notify_all();
-
-    Ok(max_read)
+    Ok(read)
   }
 }
 
 impl Write for Stream {
   fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-    let (mutex, condvar) = &*self.inner;
-    let mut buffer = mutex.lock().expect("Mutex was poisoned");
+    let shared = &*self.inner;
+    let length = self.write_len(buf);
+
+    // Whether the write has to wait for the reader, a write is always allowed into an empty buffer
+    let must_wait = || {
+      let buffered = shared.ring.len();
+
+      buffered > 0 && buffered + length > shared.target.load(Ordering::Relaxed)
+    };
+
+    if must_wait() {
+      shared.blocked_writes.fetch_add(1, Ordering::Relaxed);
+
+      let blocked_since = Instant::now();
+      let flushes = shared.flushes.load(Ordering::Acquire);
+      *shared.writer.lock().expect("Mutex was poisoned") = Some(std::thread::current());
+
+      loop {
+        shared.writer_waiting.store(true, Ordering::SeqCst);
+        fence(Ordering::SeqCst);
+
+        if !must_wait() {
+          break;
+        }
+
+        // The stream was flushed while we were waiting, so this data would be discarded anyway
+        if shared.flushes.load(Ordering::Acquire) != flushes {
+          shared.writer_waiting.store(false, Ordering::SeqCst);
+          shared
+            .telemetry
+            .record_blocked_write(blocked_since.elapsed());
+
+          return Ok(buf.len());
+        }
+
+        std::thread::park_timeout(PARK_TIMEOUT);
+      }
 
-    while buffer.len() + buf.len() > MAX_SIZE {
-      buffer = condvar.wait(buffer).expect("Mutex was poisoned");
+      shared.writer_waiting.store(false, Ordering::SeqCst);
+      shared
+        .telemetry
+        .record_blocked_write(blocked_since.elapsed());
     }
 
-    buffer.extend_from_slice(buf);
-    condvar.notify_all();
+    shared.ring.push(&buf[..length]);
+    shared.primed.store(true, Ordering::Relaxed);
 
-    Ok(buf.len())
+    Ok(length)
   }
 
   fn flush(&mut self) -> std::io::Result<()> {
-    let (mutex, condvar) = &*self.inner;
-    let mut buffer = mutex.lock().expect("Mutex was poisoned");
+    let shared = &*self.inner;
 
-    buffer.clear();
-    condvar.notify_all();
+    // Only the reader may move the read position, so it discards the data on its next read
+    shared.flush_to.store(shared.ring.tail(), Ordering::Release);
+    shared.primed.store(false, Ordering::Relaxed);
+    shared.flushes.fetch_add(1, Ordering::AcqRel);
+    shared.wake_writer();
 
     Ok(())
   }
@@ -0,0 +1,199 @@
+use std::f32::consts::PI;
+
+/// The length of the windows that are overlapped, in frames (~21ms at 48 kHz)
+const WINDOW: usize = 1024;
+
+/// The distance between consecutive windows in the output, in frames
+const HOP: usize = WINDOW / 2;
+
+/// How far (in frames) a window may be moved to line it up with the audio before it
+const TOLERANCE: usize = 256;
+
+/// Changes the speed of interleaved stereo audio without changing its pitch
+///
+/// This uses WSOLA: windows of the input are overlapped at a fixed distance in the output, while
+/// the distance between them in the input depends on the speed. Every window is shifted slightly
+/// to the position where it lines up best with the audio that came before it, which prevents the
+/// phase cancellation a plain overlap-add would cause.
+pub struct TimeStretcher {
+  speed: f64,
+
+  /// Interleaved input that may still be used by upcoming windows
+  input: Vec<f32>,
+
+  /// Where the next window ideally starts, in frames into `input`
+  position: f64,
+
+  /// Where the previous window started, in frames into `input`
+  previous: Option<usize>,
+
+  /// The second half of the previous window, which is added to the first half of the next one
+  overlap: Vec<f32>,
+
+  /// A Hann window, which adds up to a constant gain when overlapped by half
+  window: Vec<f32>,
+}
+
+impl TimeStretcher {
+  pub fn new() -> Self {
+    Self {
+      speed: 1.0,
+      input: Vec::new(),
+      position: 0.0,
+      previous: None,
+      overlap: vec![0.0; HOP * 2],
+      window: (0..WINDOW)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / WINDOW as f32).cos())
+        .collect(),
+    }
+  }
+
+  /// Change the speed, returning any audio that was still held back
+  pub fn set_speed(&mut self, speed: f64) -> Vec<f32> {
+    if speed == self.speed {
+      return Vec::new();
+    }
+
+    let previous = std::mem::replace(&mut self.speed, speed);
+
+    if speed == 1.0 {
+      self.drain()
+    } else {
+      if previous == 1.0 {
+        self.reset();
+      }
+
+      Vec::new()
+    }
+  }
+
+  /// Stretch a block of samples, returning the audio that is ready
+  pub fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+    if self.speed == 1.0 {
+      return samples.to_vec();
+    }
+
+    self.input.extend_from_slice(samples);
+
+    let mut output = Vec::new();
+
+    loop {
+      let target = self.position.round() as usize;
+
+      // The furthest window that may be picked must be complete
+      if (target + TOLERANCE + WINDOW) * 2 > self.input.len() {
+        break;
+      }
+
+      let start = match self.previous {
+        Some(previous) => self.best_match(previous, target),
+        None => target,
+      };
+
+      for i in 0..HOP {
+        for channel in 0..2 {
+          let sample = self.input[(start + i) * 2 + channel];
+
+          // The first window continues where the unstretched audio left off
+          output.push(match self.previous {
+            Some(_) => self.overlap[i * 2 + channel] + sample * self.window[i],
+            None => sample,
+          });
+
+          self.overlap[i * 2 + channel] =
+            self.input[(start + HOP + i) * 2 + channel] * self.window[HOP + i];
+        }
+      }
+
+      self.previous = Some(start);
+      self.position += HOP as f64 * self.speed;
+
+      // Discard the input that no upcoming window can reach anymore
+      let used = usize::min(start, (self.position as usize).saturating_sub(TOLERANCE));
+      if used >= WINDOW {
+        self.input.drain(..used * 2);
+        self.position -= used as f64;
+        self.previous = Some(start - used);
+      }
+    }
+
+    output
+  }
+
+  /// Discard all state, used when the output is interrupted (e.g. pausing or seeking)
+  pub fn reset(&mut self) {
+    self.input.clear();
+    self.position = 0.0;
+    self.previous = None;
+  }
+
+  /// Finish the previous window with the audio that follows it, and release the rest of the input
+  fn drain(&mut self) -> Vec<f32> {
+    let mut output = Vec::new();
+
+    match self.previous {
+      Some(previous) => {
+        let from = previous + HOP;
+        let available = (self.input.len() / 2).saturating_sub(from);
+
+        for i in 0..usize::min(HOP, available) {
+          for channel in 0..2 {
+            output.push(
+              self.overlap[i * 2 + channel] + self.input[(from + i) * 2 + channel] * self.window[i],
+            );
+          }
+        }
+
+        if available > HOP {
+          output.extend_from_slice(&self.input[(from + HOP) * 2..]);
+        }
+      }
+      None => {
+        let from = usize::min(self.position.round() as usize * 2, self.input.len());
+        output.extend_from_slice(&self.input[from..]);
+      }
+    }
+
+    self.reset();
+
+    output
+  }
+
+  /// Find the window start near `target` that lines up best with the audio following `previous`
+  fn best_match(&self, previous: usize, target: usize) -> usize {
+    // Only every few frames are compared, which is plenty to find the right alignment
+    const STRIDE: usize = 4;
+
+    let mono = |frame: usize| self.input[frame * 2] + self.input[frame * 2 + 1];
+    let natural = previous + HOP;
+
+    let mut best = target;
+    let mut best_score = f32::MIN;
+
+    for candidate in (target.saturating_sub(TOLERANCE)..=target + TOLERANCE).step_by(2) {
+      let mut correlation = 0.0;
+      let mut energy = 0.0;
+
+      for i in (0..HOP).step_by(STRIDE) {
+        let sample = mono(candidate + i);
+
+        correlation += sample * mono(natural + i);
+        energy += sample * sample;
+      }
+
+      let score = correlation / (energy + 1e-6).sqrt();
+      if score > best_score {
+        best = candidate;
+        best_score = score;
+      }
+    }
+
+    best
+  }
+}
+
+impl Default for TimeStretcher {
+  fn default() -> Self {
+    Self::new()
+  }
+}
@@ -0,0 +1,126 @@
+use std::{
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+  },
+  time::Duration,
+};
+
+/// A snapshot of the audio pipeline counters of a session
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AudioTelemetry {
+  /// The amount of (48 kHz, interleaved) samples written by the sink
+  pub samples_written: u64,
+
+  /// The amount of bytes read from the stream by songbird
+  pub bytes_read: u64,
+
+  /// The amount of reads that found no audio and had to be filled with silence
+  pub zero_fill_reads: u64,
+
+  /// The total time the sink spent waiting for the reader to make room in the stream
+  pub blocked_write_time: Duration,
+
+  /// The total time spent resampling, and the amount of packets that were resampled
+  pub resample_time: Duration,
+  pub resample_count: u64,
+
+  /// The amount of `SinkEvent::Start` and `SinkEvent::Stop` transitions
+  pub starts: u64,
+  pub stops: u64,
+
+  /// The current target size of the stream buffer, in milliseconds
+  pub buffer_target_ms: u64,
+}
+
+impl AudioTelemetry {
+  /// The average time it took to resample a single packet
+  pub fn average_resample_time(&self) -> Duration {
+    match self.resample_count {
+      0 => Duration::ZERO,
+      count => self.resample_time / count as u32,
+    }
+  }
+}
+
+/// Counters shared between a session, its stream and its sink
+///
+/// The same instance is kept across players, so the counters cover the whole session.
+#[derive(Clone, Default)]
+pub struct Telemetry(Arc<Counters>);
+
+#[derive(Default)]
+struct Counters {
+  samples_written: AtomicU64,
+  bytes_read: AtomicU64,
+  zero_fill_reads: AtomicU64,
+  blocked_write_us: AtomicU64,
+  resample_us: AtomicU64,
+  resample_count: AtomicU64,
+  starts: AtomicU64,
+  stops: AtomicU64,
+  buffer_target_ms: AtomicU64,
+}
+
+impl Telemetry {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn snapshot(&self) -> AudioTelemetry {
+    let counters = &*self.0;
+    let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+    AudioTelemetry {
+      samples_written: load(&counters.samples_written),
+      bytes_read: load(&counters.bytes_read),
+      zero_fill_reads: load(&counters.zero_fill_reads),
+      blocked_write_time: Duration::from_micros(load(&counters.blocked_write_us)),
+      resample_time: Duration::from_micros(load(&counters.resample_us)),
+      resample_count: load(&counters.resample_count),
+      starts: load(&counters.starts),
+      stops: load(&counters.stops),
+      buffer_target_ms: load(&counters.buffer_target_ms),
+    }
+  }
+
+  pub(super) fn record_write(&self, samples: usize) {
+    add(&self.0.samples_written, samples as u64);
+  }
+
+  pub(super) fn record_read(&self, bytes: usize) {
+    add(&self.0.bytes_read, bytes as u64);
+  }
+
+  pub(super) fn record_zero_fill(&self) {
+    add(&self.0.zero_fill_reads, 1);
+  }
+
+  pub(super) fn record_blocked_write(&self, time: Duration) {
+    add(&self.0.blocked_write_us, time.as_micros() as u64);
+  }
+
+  pub(super) fn record_resample(&self, time: Duration) {
+    add(&self.0.resample_us, time.as_micros() as u64);
+    add(&self.0.resample_count, 1);
+  }
+
+  pub(super) fn record_start(&self) {
+    add(&self.0.starts, 1);
+  }
+
+  pub(super) fn record_stop(&self) {
+    add(&self.0.stops, 1);
+  }
+
+  pub(super) fn set_buffer_target(&self, target_ms: usize) {
+    self
+      .0
+      .buffer_target_ms
+      .store(target_ms as u64, Ordering::Relaxed);
+  }
+}
+
+fn add(counter: &AtomicU64, value: u64) {
+  counter.fetch_add(value, Ordering::Relaxed);
+}
@@ -0,0 +1,620 @@
+// Feeds synthetic audio through `StreamSink` and reads it back through `Stream` like songbird does
+//
+// Everything runs in-process, without Discord or Spotify.
+
+use std::{
+  f64::consts::TAU,
+  io::{Read, Write},
+  time::{Duration, Instant},
+};
+
+use librespot::playback::{
+  audio_backend::Sink, convert::Converter, decoder::AudioPacket, SAMPLE_RATE,
+};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use super::{
+  channels::ChannelMix,
+  fade::{FadeConfig, Fader},
+  resampler::TARGET_SAMPLE_RATE,
+  settings::AudioSettings,
+  stream::{JitterBounds, Stream},
+  telemetry::Telemetry,
+  SinkEvent, StreamSink,
+};
+
+/// The amount of stereo frames in a packet coming out of librespot's decoder
+const PACKET_FRAMES: usize = 2048;
+
+/// The largest Opus packet songbird accepts
+const MAX_PACKET_SIZE: usize = 1275;
+
+/// Generate interleaved stereo samples at librespot's sample rate
+fn generate(seconds: f64, mut sample: impl FnMut(f64) -> [f64; 2]) -> Vec<f64> {
+  let frames = (SAMPLE_RATE as f64 * seconds).round() as usize;
+
+  (0..frames)
+    .flat_map(|i| sample(i as f64 / SAMPLE_RATE as f64))
+    .collect()
+}
+
+fn sine(freq: f64, amplitude: f64) -> impl FnMut(f64) -> [f64; 2] {
+  move |time| {
+    let sample = (TAU * freq * time).sin() * amplitude;
+    [sample, sample]
+  }
+}
+
+/// A sine that sweeps from `from` to `to` Hz over `seconds`
+fn sweep(from: f64, to: f64, seconds: f64) -> impl FnMut(f64) -> [f64; 2] {
+  move |time| {
+    let rate = (to - from) / seconds;
+    let sample = (TAU * (from * time + rate * time * time / 2.0)).sin() * 0.5;
+    [sample, sample]
+  }
+}
+
+/// Expected amount of output frames for an amount of input frames
+fn resampled_frames(frames: usize) -> usize {
+  frames * TARGET_SAMPLE_RATE as usize / SAMPLE_RATE as usize
+}
+
+/// Assert that the amount of output frames matches the input, give or take the resampler's latency
+fn assert_frames(frames: usize, input_frames: usize) {
+  let expected = resampled_frames(input_frames);
+
+  assert!(
+    frames <= expected + 16 && frames + 1024 >= expected,
+    "expected about {expected} frames, got {frames}"
+  );
+}
+
+/// Read everything that is buffered, as 32-bit float PCM
+fn read_pcm(stream: &mut Stream) -> Vec<f32> {
+  let mut samples = vec![];
+  let mut bytes = vec![0u8; 4096];
+
+  loop {
+    let length = usize::min(stream.buffered(), bytes.len()) / 4 * 4;
+    if length == 0 {
+      break;
+    }
+
+    stream
+      .read_exact(&mut bytes[..length])
+      .expect("to read audio");
+
+    samples.extend(
+      bytes[..length]
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+    );
+  }
+
+  samples
+}
+
+/// Read everything that is buffered, as DCA framed Opus packets
+fn read_frames(stream: &mut Stream) -> Vec<Vec<u8>> {
+  let mut frames = vec![];
+
+  while stream.buffered() > 0 {
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header).expect("to read a header");
+
+    let mut frame = vec![0u8; i16::from_le_bytes(header) as usize];
+    stream.read_exact(&mut frame).expect("to read a frame");
+
+    frames.push(frame);
+  }
+
+  frames
+}
+
+fn left(samples: &[f32]) -> impl Iterator<Item = f32> + '_ {
+  samples.iter().step_by(2).copied()
+}
+
+fn right(samples: &[f32]) -> impl Iterator<Item = f32> + '_ {
+  samples.iter().skip(1).step_by(2).copied()
+}
+
+fn rms(samples: impl Iterator<Item = f32>) -> f32 {
+  let (sum, count) = samples.fold((0.0, 0), |(sum, count), sample| {
+    (sum + sample * sample, count + 1)
+  });
+
+  (sum / count as f32).sqrt()
+}
+
+/// Estimate the frequency of a channel by counting rising zero crossings
+fn frequency(samples: impl Iterator<Item = f32>) -> f64 {
+  let samples = samples.collect::<Vec<_>>();
+  let crossings = samples
+    .windows(2)
+    .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+    .count();
+
+  crossings as f64 * TARGET_SAMPLE_RATE as f64 / samples.len() as f64
+}
+
+struct Harness {
+  sink: StreamSink,
+  reader: Stream,
+  converter: Converter,
+  events: UnboundedReceiver<SinkEvent>,
+}
+
+impl Harness {
+  fn new(stream: Stream) -> Self {
+    let (tx, events) = tokio::sync::mpsc::unbounded_channel();
+
+    Self {
+      sink: StreamSink::new(stream.clone(), tx, AudioSettings::new()),
+      reader: stream,
+      converter: Converter::new(None),
+      events,
+    }
+    .with_fades(0, 0)
+  }
+
+  /// Override the fades from the environment, with lengths in milliseconds
+  fn with_fades(mut self, fade: u64, crossfade: u64) -> Self {
+    self.sink.fader = Fader::new(FadeConfig {
+      fade: Duration::from_millis(fade),
+      crossfade: Duration::from_millis(crossfade),
+    });
+
+    self
+  }
+
+  fn pcm() -> Self {
+    Self::new(Stream::new(Telemetry::new()))
+  }
+
+  fn framed() -> Self {
+    Self::new(Stream::new_framed(Telemetry::new()))
+  }
+
+  /// Write a single packet, without reading anything back
+  fn write_packet(&mut self, samples: &[f64]) {
+    self
+      .sink
+      .write(AudioPacket::Samples(samples.to_vec()), &mut self.converter)
+      .expect("to write the packet");
+  }
+
+  /// Write the samples in packets of `frames` frames, reading back the PCM after every packet
+  fn feed_pcm(&mut self, samples: &[f64], frames: usize) -> Vec<f32> {
+    let mut output = vec![];
+
+    for packet in samples.chunks(frames * 2) {
+      self.write_packet(packet);
+      output.extend(read_pcm(&mut self.reader));
+    }
+
+    output
+  }
+
+  /// Write the samples in librespot sized packets, reading back the Opus frames after every packet
+  fn feed_framed(&mut self, samples: &[f64]) -> Vec<Vec<u8>> {
+    let mut output = vec![];
+
+    for packet in samples.chunks(PACKET_FRAMES * 2) {
+      self.write_packet(packet);
+      output.extend(read_frames(&mut self.reader));
+    }
+
+    output
+  }
+}
+
+#[test]
+fn resamples_to_48_khz() {
+  let mut harness = Harness::pcm();
+  let input = generate(2.0, sine(1000.0, 0.5));
+  let output = harness.feed_pcm(&input, PACKET_FRAMES);
+
+  assert_frames(output.len() / 2, input.len() / 2);
+
+  // Played back at 48 kHz, the sine must still be 1 kHz
+  let settled = &output[TARGET_SAMPLE_RATE as usize / 10 * 2..];
+  let freq = frequency(left(settled));
+  assert!((freq - 1000.0).abs() < 10.0, "expected 1000 Hz, got {freq}");
+}
+
+#[test]
+fn keeps_channels_apart() {
+  let mut harness = Harness::pcm();
+  let mut tone = sine(440.0, 0.5);
+  let input = generate(1.0, |time| [tone(time)[0], 0.0]);
+  let output = harness.feed_pcm(&input, PACKET_FRAMES);
+
+  assert_eq!(
+    output.len() % 2,
+    0,
+    "output must consist of whole stereo frames"
+  );
+
+  let settled = &output[2048..];
+  let (left_rms, right_rms) = (rms(left(settled)), rms(right(settled)));
+
+  assert!(
+    (left_rms - 0.354).abs() < 0.02,
+    "left channel RMS is {left_rms}"
+  );
+  assert!(right_rms < 0.001, "right channel RMS is {right_rms}");
+}
+
+#[test]
+fn mixes_channels() {
+  let mut harness = Harness::pcm();
+  let mut tone = sine(440.0, 0.5);
+  let input = generate(1.0, |time| [tone(time)[0], 0.0]);
+
+  // Mono plays the left channel on both sides, at half the level
+  harness.sink.settings.set_channel_mix(ChannelMix {
+    mono: true,
+    balance: 0,
+  });
+
+  let output = harness.feed_pcm(&input, PACKET_FRAMES);
+  let settled = &output[2048..];
+
+  assert!(left(settled).eq(right(settled)));
+  assert!((rms(left(settled)) - 0.177).abs() < 0.01);
+
+  // Turning the balance fully to the right silences the left channel
+  harness.sink.settings.set_channel_mix(ChannelMix {
+    mono: true,
+    balance: 100,
+  });
+
+  let output = harness.feed_pcm(&input, PACKET_FRAMES);
+  let settled = &output[2048..];
+
+  assert!(rms(left(settled)) < 0.001);
+  assert!((rms(right(settled)) - 0.177).abs() < 0.01);
+}
+
+#[test]
+fn is_continuous_across_packets() {
+  // Odd packet sizes make packet boundaries land anywhere within a resampler block
+  for frames in [PACKET_FRAMES, 333, 1] {
+    let mut harness = Harness::pcm();
+    let input = generate(1.0, sweep(100.0, 2000.0, 1.0));
+    let output = harness.feed_pcm(&input, frames);
+
+    // At 2 kHz and an amplitude of 0.5, consecutive samples differ by at most ~0.13
+    let jump = left(&output[2048..])
+      .collect::<Vec<_>>()
+      .windows(2)
+      .map(|pair| (pair[1] - pair[0]).abs())
+      .fold(0.0, f32::max);
+
+    assert!(
+      jump < 0.15,
+      "discontinuity of {jump} with {frames} frame packets"
+    );
+  }
+}
+
+#[test]
+fn silence_stays_silent() {
+  let mut harness = Harness::pcm();
+  let input = generate(1.0, |_| [0.0, 0.0]);
+  let output = harness.feed_pcm(&input, PACKET_FRAMES);
+
+  assert!(!output.is_empty());
+  assert!(output.iter().all(|sample| sample.abs() < 1e-6));
+}
+
+#[test]
+fn bursts_are_not_lost() {
+  let mut harness = Harness::pcm();
+  let mut written = 0;
+  let mut read = 0;
+
+  // Alternate loud bursts and silence in packets of very different sizes
+  for (i, frames) in [4096, 16, 2048, 1, 777, 4096, 300, 2048]
+    .iter()
+    .cycle()
+    .take(64)
+    .enumerate()
+  {
+    let amplitude = if i % 2 == 0 { 0.9 } else { 0.0 };
+    let input = generate(*frames as f64 / SAMPLE_RATE as f64, sine(440.0, amplitude));
+
+    written += input.len() / 2;
+    read += harness.feed_pcm(&input, *frames).len() / 2;
+  }
+
+  assert_frames(read, written);
+}
+
+#[test]
+fn stop_without_fades_flushes_buffered_audio() {
+  let mut harness = Harness::pcm();
+
+  harness.sink.start().expect("to start");
+  assert!(matches!(harness.events.try_recv(), Ok(SinkEvent::Start)));
+
+  harness.write_packet(&generate(0.05, sine(440.0, 0.5)));
+  assert!(harness.reader.buffered() > 0);
+
+  harness.sink.stop().expect("to stop");
+  assert!(matches!(harness.events.try_recv(), Ok(SinkEvent::Stop)));
+
+  // The reader gets silence instead of the audio that was written before the stop
+  let mut bytes = vec![0xAAu8; 4096];
+  harness.reader.read_exact(&mut bytes).expect("to read");
+
+  assert!(bytes.iter().all(|byte| *byte == 0));
+  assert_eq!(harness.reader.buffered(), 0);
+
+  let telemetry = harness.reader.telemetry().snapshot();
+  assert_eq!(telemetry.starts, 1);
+  assert_eq!(telemetry.stops, 1);
+  assert_eq!(telemetry.zero_fill_reads, 1);
+
+  // Stopping while paused is not a starvation
+  assert_eq!(harness.reader.stats().underruns, 0);
+
+  // Audio written after the stop comes through again
+  let output = harness.feed_pcm(&generate(0.5, sine(440.0, 0.5)), PACKET_FRAMES);
+  assert!(rms(left(&output[2048..])) > 0.3);
+}
+
+#[test]
+fn fades_in_and_out() {
+  let mut harness = Harness::pcm().with_fades(20, 0);
+  let input = generate(0.5, sine(440.0, 0.5));
+
+  harness.sink.start().expect("to start");
+  let mut output = harness.feed_pcm(&input, PACKET_FRAMES);
+
+  // The fade-in starts from silence, and is done after 20ms
+  assert!(rms(left(&output[..2 * 96])) < 0.02);
+  assert!(rms(left(&output[2 * 960..2 * 4800])) > 0.3);
+
+  // Stopping writes the faded out tail after the buffered audio, instead of discarding it
+  harness.sink.stop().expect("to stop");
+  output.extend(read_pcm(&mut harness.reader));
+
+  assert_frames(output.len() / 2, input.len() / 2);
+  assert!(rms(left(&output[output.len() - 2 * 96..])) < 0.02);
+
+  // Running dry after the tail is not a starvation
+  let mut bytes = vec![0u8; 4096];
+  harness.reader.read_exact(&mut bytes).expect("to read");
+  assert_eq!(harness.reader.stats().underruns, 0);
+}
+
+#[test]
+fn crossfades_consecutive_tracks() {
+  let mut harness = Harness::pcm().with_fades(0, 100);
+  let first = generate(0.5, sine(440.0, 0.5));
+  let second = generate(0.5, |_| [0.0, 0.0]);
+
+  let mut output = harness.feed_pcm(&first, PACKET_FRAMES);
+  let boundary = output.len();
+
+  harness.sink.settings.signal_track_change();
+  output.extend(harness.feed_pcm(&second, PACKET_FRAMES));
+
+  // The end of the first track overlaps the start of the second one, and the end of the second one
+  // is still held back
+  assert_frames(
+    output.len() / 2 + 2 * 4800,
+    (first.len() + second.len()) / 2,
+  );
+
+  // ...and fades out over the length of the crossfade, instead of stopping abruptly
+  let crossfade = &output[boundary..boundary + 2 * 4800];
+  assert!(rms(left(&crossfade[..2 * 1200])) > 0.2);
+  assert!(rms(left(&crossfade[2 * 3600..])) < 0.15);
+  assert!(rms(left(&output[boundary + 2 * 4800..])) < 0.001);
+}
+
+#[test]
+fn speeds_up_episodes_without_changing_pitch() {
+  let mut harness = Harness::pcm();
+  let input = generate(2.0, sine(1000.0, 0.5));
+
+  harness.sink.settings.set_speed(2.0);
+
+  // Music is never sped up
+  let output = harness.feed_pcm(&input, PACKET_FRAMES);
+  assert_frames(output.len() / 2, input.len() / 2);
+
+  harness.sink.settings.set_episode(true);
+
+  let output = harness.feed_pcm(&input, PACKET_FRAMES);
+  assert_frames(output.len() / 2, input.len() / 4);
+
+  let settled = &output[2048..];
+  let freq = frequency(left(settled));
+  assert!((freq - 1000.0).abs() < 10.0, "expected 1000 Hz, got {freq}");
+
+  // Overlapping the windows must not make the sine quieter or louder
+  let level = rms(left(settled));
+  assert!((level - 0.354).abs() < 0.03, "RMS is {level}");
+}
+
+#[test]
+fn writer_waits_for_reader() {
+  let stream = Stream::new(Telemetry::new());
+  let input = generate(2.0, sine(440.0, 0.5));
+
+  // librespot writes from its own thread, which blocks while the buffer is full
+  let writer = std::thread::spawn({
+    let stream = stream.clone();
+    let input = input.clone();
+
+    move || {
+      let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+      let mut sink = StreamSink::new(stream, tx, AudioSettings::new());
+      let mut converter = Converter::new(None);
+
+      for packet in input.chunks(PACKET_FRAMES * 2) {
+        sink
+          .write(AudioPacket::Samples(packet.to_vec()), &mut converter)
+          .expect("to write the packet");
+      }
+    }
+  });
+
+  // Wait for the writer to fill the buffer, however long that takes on a busy machine
+  let deadline = Instant::now() + Duration::from_secs(10);
+
+  while stream.stats().blocked_writes == 0 {
+    assert!(
+      Instant::now() < deadline,
+      "writer never waited for the reader"
+    );
+    std::thread::sleep(Duration::from_millis(1));
+  }
+
+  let mut reader = stream;
+  let stats = reader.stats();
+
+  assert!(!writer.is_finished(), "writer did not wait for the reader");
+
+  // One packet may go into an empty buffer regardless of the target
+  let packet_bytes = resampled_frames(PACKET_FRAMES) * 2 * 4;
+  let target_bytes = stats.target_ms * 48 * 2 * 4;
+  assert!(reader.buffered() <= usize::max(target_bytes, packet_bytes + 64));
+
+  // Once the reader catches up, everything that was written arrives
+  let deadline = Instant::now() + Duration::from_secs(10);
+  let mut frames = 0;
+
+  while !writer.is_finished() || reader.buffered() > 0 {
+    assert!(Instant::now() < deadline, "writer never finished");
+
+    frames += read_pcm(&mut reader).len() / 2;
+    std::thread::sleep(Duration::from_millis(1));
+  }
+
+  writer.join().expect("writer to not panic");
+
+  assert_frames(frames, input.len() / 2);
+  assert!(reader.telemetry().snapshot().blocked_write_time > Duration::ZERO);
+}
+
+#[test]
+fn opus_frames_are_well_formed() {
+  let mut harness = Harness::framed();
+  let frames = harness.feed_framed(&generate(1.0, sine(440.0, 0.5)));
+
+  // 20ms frames, minus what the resampler and encoder still hold on to
+  assert!(
+    (48..=50).contains(&frames.len()),
+    "expected about 50 frames, got {}",
+    frames.len()
+  );
+
+  assert!(frames
+    .iter()
+    .all(|frame| !frame.is_empty() && frame.len() <= MAX_PACKET_SIZE));
+}
+
+#[test]
+fn small_framed_buffer_fits_the_largest_frame() {
+  let mut stream = Stream::with_bounds(
+    JitterBounds {
+      floor_ms: 1,
+      ceiling_ms: 1,
+    },
+    true,
+    Telemetry::new(),
+  );
+
+  let mut frame = (MAX_PACKET_SIZE as i16).to_le_bytes().to_vec();
+  frame.extend([0x55; MAX_PACKET_SIZE]);
+
+  stream.write_all(&frame).expect("to write the frame");
+
+  assert_eq!(read_frames(&mut stream), vec![vec![0x55; MAX_PACKET_SIZE]]);
+}
+
+#[test]
+fn framed_stream_reads_silence_when_empty() {
+  let mut harness = Harness::framed();
+
+  // songbird keeps reading while nothing is being played, which must yield silence frames
+  let mut header = [0u8; 2];
+  harness
+    .reader
+    .read_exact(&mut header)
+    .expect("to read a header");
+
+  let mut frame = vec![0u8; i16::from_le_bytes(header) as usize];
+  harness
+    .reader
+    .read_exact(&mut frame)
+    .expect("to read a frame");
+
+  assert_eq!(frame, super::encoder::SILENCE_FRAME);
+  assert_eq!(harness.reader.telemetry().snapshot().zero_fill_reads, 1);
+}
+
+#[test]
+fn survives_a_dropped_player() {
+  let mut harness = Harness::pcm();
+  drop(harness.events);
+
+  // Returning an error here would make librespot exit the whole process
+  assert!(harness.sink.start().is_ok());
+  assert!(harness
+    .sink
+    .write(
+      AudioPacket::Samples(generate(0.05, sine(440.0, 0.5))),
+      &mut harness.converter
+    )
+    .is_ok());
+  assert!(harness.sink.stop().is_ok());
+
+  // A failed sink no longer produces audio
+  assert!(read_pcm(&mut harness.reader).is_empty());
+}
+
+#[test]
+fn reconnected_player_keeps_feeding_the_stream() {
+  let mut harness = Harness::pcm();
+
+  harness.sink.start().expect("to start");
+  let output = harness.feed_pcm(&generate(0.5, sine(440.0, 0.5)), PACKET_FRAMES);
+  assert!(rms(left(&output[2048..])) > 0.3);
+
+  // After losing the session the old sink is dropped as-is, and the next one reuses the stream
+  let (tx, events) = tokio::sync::mpsc::unbounded_channel();
+  harness.sink = StreamSink::new(harness.reader.clone(), tx, AudioSettings::new());
+  harness.events = events;
+  let mut harness = harness.with_fades(0, 0);
+
+  harness.sink.start().expect("to start");
+  assert!(matches!(harness.events.try_recv(), Ok(SinkEvent::Start)));
+
+  let output = harness.feed_pcm(&generate(0.5, sine(440.0, 0.5)), PACKET_FRAMES);
+  assert!(rms(left(&output[2048..])) > 0.3);
+  assert_eq!(harness.reader.telemetry().snapshot().starts, 2);
+}
+
+#[test]
+fn pause_latency_does_not_depend_on_crossfade() {
+  for crossfade in [0, 1000, 3000] {
+    let mut harness = Harness::pcm().with_fades(20, crossfade);
+
+    harness.sink.start().expect("to start");
+    harness.feed_pcm(&generate(4.0, sine(440.0, 0.5)), PACKET_FRAMES);
+
+    // Only the 20ms fade-out plays after stopping, what was held back for crossfading is dropped
+    harness.sink.stop().expect("to stop");
+    let tail = read_pcm(&mut harness.reader);
+
+    assert_eq!(tail.len() / 2, 960, "crossfade of {crossfade}ms");
+    assert!(rms(left(&tail[..2 * 96])) > 0.3);
+    assert!(rms(left(&tail[tail.len() - 2 * 96..])) < 0.02);
+  }
+}
@@ -0,0 +1,127 @@
+use log::error;
+use reqwest::StatusCode;
+use serenity::{
+  builder::CreateApplicationCommand,
+  model::prelude::{
+    command::CommandOptionType, interaction::application_command::ApplicationCommandInteraction,
+  },
+  prelude::Context,
+};
+
+use crate::{
+  bot::commands::{respond_message, CommandOutput},
+  database::{Database, DatabaseError},
+  utils::embed::{EmbedBuilder, Status},
+};
+
+pub const NAME: &str = "autotransfer";
+
+pub fn command(ctx: Context, command: ApplicationCommandInteraction) -> CommandOutput {
+  Box::pin(async move {
+    let data = ctx.data.read().await;
+    let database = data.get::<Database>().expect("to contain a value");
+
+    // Check if user exists, if not, create them
+    if let Err(why) = database.get_user(command.user.id.to_string()).await {
+      let created = match why {
+        DatabaseError::InvalidStatusCode(StatusCode::NOT_FOUND) => {
+          match database.create_user(command.user.id.to_string()).await {
+            Ok(_) => true,
+            Err(why) => {
+              error!("Error creating user: {:?}", why);
+              false
+            }
+          }
+        }
+        _ => false,
+      };
+
+      if !created {
+        respond_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .description("Something went wrong while trying to update your settings.")
+            .status(Status::Error)
+            .build(),
+          true,
+        )
+        .await;
+
+        return;
+      }
+    }
+
+    let enabled = match command
+      .data
+      .options
+      .get(0)
+      .and_then(|option| option.value.as_ref())
+      .and_then(|value| value.as_bool())
+    {
+      Some(enabled) => enabled,
+      None => {
+        respond_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .description("You need to provide whether to enable automatic transfers.")
+            .status(Status::Error)
+            .build(),
+          true,
+        )
+        .await;
+
+        return;
+      }
+    };
+
+    if let Err(why) = database
+      .update_user_auto_transfer(command.user.id.to_string(), enabled)
+      .await
+    {
+      error!("Error updating user auto transfer: {:?}", why);
+
+      respond_message(
+        &ctx,
+        &command,
+        EmbedBuilder::new()
+          .description("Something went wrong while trying to update your settings.")
+          .status(Status::Error)
+          .build(),
+        true,
+      )
+      .await;
+
+      return;
+    }
+
+    respond_message(
+      &ctx,
+      &command,
+      EmbedBuilder::new()
+        .description(if enabled {
+          "Your Spotify playback will now be moved to Spoticord when it joins a voice channel"
+        } else {
+          "You will now have to select your Spoticord device in Spotify yourself"
+        })
+        .status(Status::Success)
+        .build(),
+      true,
+    )
+    .await;
+  })
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+  command
+    .name(NAME)
+    .description("Choose whether your Spotify playback moves to Spoticord when it joins")
+    .create_option(|option| {
+      option
+        .name("enabled")
+        .description("Whether to move your playback automatically")
+        .kind(CommandOptionType::Boolean)
+        .required(true)
+    })
+}
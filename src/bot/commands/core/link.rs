@@ -101,6 +101,14 @@ pub fn command(ctx: Context, command: ApplicationCommandInteraction) -> CommandO
       }
     }
 
+    // Make sure a login stored for a previously linked account is not reused for the new one
+    if let Err(why) = database
+      .delete_user_credentials(command.user.id.to_string())
+      .await
+    {
+      error!("Error deleting user credentials: {:?}", why);
+    }
+
     match database
       .create_user_request(command.user.id.to_string())
       .await
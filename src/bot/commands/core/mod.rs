@@ -1,3 +1,4 @@
+pub mod autotransfer;
 pub mod help;
 pub mod link;
 pub mod rename;
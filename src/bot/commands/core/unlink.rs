@@ -63,6 +63,14 @@ pub fn command(ctx: Context, command: ApplicationCommandInteraction) -> CommandO
       return;
     }
 
+    // The stored login belongs to the unlinked account, it must not be used for the next one
+    if let Err(why) = database
+      .delete_user_credentials(command.user.id.to_string())
+      .await
+    {
+      error!("Error deleting user credentials: {:?}", why);
+    }
+
     respond_message(
       &ctx,
       &command,
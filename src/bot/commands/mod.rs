@@ -6,7 +6,7 @@ use serenity::{
   model::application::command::Command,
   model::prelude::{
     interaction::{
-      application_command::ApplicationCommandInteraction,
+      application_command::ApplicationCommandInteraction, autocomplete::AutocompleteInteraction,
       message_component::MessageComponentInteraction, InteractionResponseType,
     },
     GuildId,
@@ -104,6 +104,7 @@ pub async fn defer_message(
 pub type CommandOutput = Pin<Box<dyn Future<Output = ()> + Send>>;
 pub type CommandExecutor = fn(Context, ApplicationCommandInteraction) -> CommandOutput;
 pub type ComponentExecutor = fn(Context, MessageComponentInteraction) -> CommandOutput;
+pub type AutocompleteExecutor = fn(Context, AutocompleteInteraction) -> CommandOutput;
 
 #[derive(Clone)]
 pub struct CommandManager {
@@ -115,6 +116,7 @@ pub struct CommandInfo {
   pub name: String,
   pub command_executor: CommandExecutor,
   pub component_executor: Option<ComponentExecutor>,
+  pub autocomplete_executor: Option<AutocompleteExecutor>,
   pub register: fn(&mut CreateApplicationCommand) -> &mut CreateApplicationCommand,
 }
 
@@ -127,8 +129,8 @@ impl CommandManager {
     // Debug-only commands
     #[cfg(debug_assertions)]
     {
-      instance.insert(ping::NAME, ping::register, ping::command, None);
-      instance.insert(token::NAME, token::register, token::command, None);
+      instance.insert(ping::NAME, ping::register, ping::command, None, None);
+      instance.insert(token::NAME, token::register, token::command, None, None);
     }
 
     // Core commands
@@ -137,30 +139,42 @@ impl CommandManager {
       core::help::register,
       core::help::command,
       None,
+      None,
     );
     instance.insert(
       core::version::NAME,
       core::version::register,
       core::version::command,
       None,
+      None,
     );
     instance.insert(
       core::link::NAME,
       core::link::register,
       core::link::command,
       None,
+      None,
     );
     instance.insert(
       core::unlink::NAME,
       core::unlink::register,
       core::unlink::command,
       None,
+      None,
     );
     instance.insert(
       core::rename::NAME,
       core::rename::register,
       core::rename::command,
       None,
+      None,
+    );
+    instance.insert(
+      core::autotransfer::NAME,
+      core::autotransfer::register,
+      core::autotransfer::command,
+      None,
+      None,
     );
 
     // Music commands
@@ -169,18 +183,98 @@ impl CommandManager {
       music::join::register,
       music::join::command,
       None,
+      None,
     );
     instance.insert(
       music::leave::NAME,
       music::leave::register,
       music::leave::command,
       None,
+      None,
     );
     instance.insert(
       music::playing::NAME,
       music::playing::register,
       music::playing::command,
       Some(music::playing::component),
+      None,
+    );
+    instance.insert(
+      music::eq::NAME,
+      music::eq::register,
+      music::eq::command,
+      None,
+      None,
+    );
+    instance.insert(
+      music::normalisation::NAME,
+      music::normalisation::register,
+      music::normalisation::command,
+      None,
+      None,
+    );
+    instance.insert(
+      music::volume::NAME,
+      music::volume::register,
+      music::volume::command,
+      None,
+      None,
+    );
+    instance.insert(
+      music::bitrate::NAME,
+      music::bitrate::register,
+      music::bitrate::command,
+      None,
+      None,
+    );
+    instance.insert(
+      music::speed::NAME,
+      music::speed::register,
+      music::speed::command,
+      None,
+      None,
+    );
+    instance.insert(
+      music::channels::NAME,
+      music::channels::register,
+      music::channels::command,
+      None,
+      None,
+    );
+    instance.insert(
+      music::play::NAME,
+      music::play::register,
+      music::play::command,
+      None,
+      None,
+    );
+    instance.insert(
+      music::queue::NAME,
+      music::queue::register,
+      music::queue::command,
+      Some(music::queue::component),
+      None,
+    );
+    instance.insert(
+      music::search::NAME,
+      music::search::register,
+      music::search::command,
+      Some(music::search::component),
+      Some(music::search::autocomplete),
+    );
+    instance.insert(
+      music::seek::NAME,
+      music::seek::register,
+      music::seek::command,
+      None,
+      None,
+    );
+    instance.insert(
+      music::mode::NAME,
+      music::mode::register,
+      music::mode::command,
+      None,
+      None,
     );
 
     instance
@@ -192,6 +286,7 @@ impl CommandManager {
     register: fn(&mut CreateApplicationCommand) -> &mut CreateApplicationCommand,
     command_executor: CommandExecutor,
     component_executor: Option<ComponentExecutor>,
+    autocomplete_executor: Option<AutocompleteExecutor>,
   ) {
     let name = name.into();
 
@@ -202,6 +297,7 @@ impl CommandManager {
         register,
         command_executor,
         component_executor,
+        autocomplete_executor,
       },
     );
   }
@@ -302,6 +398,19 @@ impl CommandManager {
       error!("Failed to respond to interaction: {}", why);
     }
   }
+
+  // On autocomplete interaction (e.g. typing in an option)
+  pub async fn execute_autocomplete(&self, ctx: &Context, interaction: AutocompleteInteraction) {
+    let executor = self
+      .commands
+      .get(&interaction.data.name)
+      .and_then(|command| command.autocomplete_executor);
+
+    // Discord shows "Loading options failed" by itself if there is no response
+    if let Some(executor) = executor {
+      executor(ctx.clone(), interaction).await;
+    }
+  }
 }
 
 impl TypeMapKey for CommandManager {
@@ -0,0 +1,128 @@
+use log::error;
+use serenity::{
+  builder::CreateApplicationCommand,
+  model::{
+    prelude::{
+      command::CommandOptionType, interaction::application_command::ApplicationCommandInteraction,
+    },
+    Permissions,
+  },
+  prelude::Context,
+};
+
+use crate::{
+  bot::commands::{respond_message, CommandOutput},
+  database::Database,
+  player::bitrate::BitrateSetting,
+  utils::embed::{EmbedBuilder, Status},
+};
+
+pub const NAME: &str = "bitrate";
+
+pub fn command(ctx: Context, command: ApplicationCommandInteraction) -> CommandOutput {
+  Box::pin(async move {
+    let data = ctx.data.read().await;
+    let database = data.get::<Database>().expect("to contain a value");
+
+    let guild_id = command.guild_id.expect("to contain a value");
+
+    // `None` means the guild goes back to the bitrate of the deployment
+    let bitrate = match command
+      .data
+      .options
+      .get(0)
+      .and_then(|option| option.value.as_ref())
+      .and_then(|value| value.as_str())
+    {
+      Some("default") => None,
+      Some(value) => match value.parse::<BitrateSetting>() {
+        Ok(bitrate) => Some(bitrate),
+        Err(_) => {
+          respond_message(
+            &ctx,
+            &command,
+            EmbedBuilder::new()
+              .description("You need to provide a valid bitrate.")
+              .status(Status::Error)
+              .build(),
+            true,
+          )
+          .await;
+
+          return;
+        }
+      },
+      None => {
+        respond_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .description("You need to provide a valid bitrate.")
+            .status(Status::Error)
+            .build(),
+          true,
+        )
+        .await;
+
+        return;
+      }
+    };
+
+    if let Err(why) = database
+      .modify_guild_settings(guild_id.to_string(), |settings| settings.bitrate = bitrate)
+      .await
+    {
+      error!("Failed to update guild settings: {:?}", why);
+
+      respond_message(
+        &ctx,
+        &command,
+        EmbedBuilder::new()
+          .description("Something went wrong while trying to save the bitrate.")
+          .status(Status::Error)
+          .build(),
+        true,
+      )
+      .await;
+
+      return;
+    }
+
+    let name = bitrate.unwrap_or_else(BitrateSetting::from_env).name();
+
+    respond_message(
+      &ctx,
+      &command,
+      EmbedBuilder::new()
+        .description(format!(
+          "The Spotify bitrate is now set to **{}**\nThis is applied the next time someone starts playing music.",
+          name
+        ))
+        .status(Status::Success)
+        .build(),
+      true,
+    )
+    .await;
+  })
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+  command
+    .name(NAME)
+    .description("Change the bitrate at which the bot streams from Spotify in this server")
+    .default_member_permissions(Permissions::MANAGE_GUILD)
+    .create_option(|option| {
+      option
+        .name("bitrate")
+        .description("The bitrate to use")
+        .kind(CommandOptionType::String)
+        .required(true)
+        .add_string_choice("Bot default", "default");
+
+      for bitrate in BitrateSetting::ALL {
+        option.add_string_choice(bitrate.name(), bitrate.id());
+      }
+
+      option
+    })
+}
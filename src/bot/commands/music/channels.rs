@@ -0,0 +1,121 @@
+use log::error;
+use serde_json::Value;
+use serenity::{
+  builder::CreateApplicationCommand,
+  model::{
+    prelude::{
+      command::CommandOptionType, interaction::application_command::ApplicationCommandInteraction,
+    },
+    Permissions,
+  },
+  prelude::Context,
+};
+
+use crate::{
+  bot::commands::{respond_message, CommandOutput},
+  database::Database,
+  session::manager::SessionManager,
+  utils::embed::{EmbedBuilder, Status},
+};
+
+pub const NAME: &str = "channels";
+
+pub fn command(ctx: Context, command: ApplicationCommandInteraction) -> CommandOutput {
+  Box::pin(async move {
+    let data = ctx.data.read().await;
+    let database = data.get::<Database>().expect("to contain a value");
+    let session_manager = data
+      .get::<SessionManager>()
+      .expect("to contain a value")
+      .clone();
+
+    let guild_id = command.guild_id.expect("to contain a value");
+
+    let option = |name: &str| -> Option<&Value> {
+      command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| option.value.as_ref())
+    };
+
+    let mono = option("mono").and_then(|value| value.as_bool());
+    let balance = option("balance")
+      .and_then(|value| value.as_i64())
+      .map(|balance| balance.clamp(-100, 100) as i8);
+
+    let mut mix = None;
+
+    let result = database
+      .modify_guild_settings(guild_id.to_string(), |settings| {
+        if let Some(mono) = mono {
+          settings.channels.mono = mono;
+        }
+
+        if let Some(balance) = balance {
+          settings.channels.balance = balance;
+        }
+
+        mix = Some(settings.channels);
+      })
+      .await;
+
+    if let Err(why) = result {
+      error!("Failed to update guild settings: {:?}", why);
+
+      respond_message(
+        &ctx,
+        &command,
+        EmbedBuilder::new()
+          .description("Something went wrong while trying to save the channel settings.")
+          .status(Status::Error)
+          .build(),
+        true,
+      )
+      .await;
+
+      return;
+    }
+
+    let mix = mix.expect("to contain a value");
+
+    // Unlike most settings, the mix can be changed during playback
+    if let Some(session) = session_manager.get_session(guild_id).await {
+      session.set_channel_mix(mix).await;
+    }
+
+    respond_message(
+      &ctx,
+      &command,
+      EmbedBuilder::new()
+        .title("Channels")
+        .description(mix.describe())
+        .status(Status::Info)
+        .build(),
+      true,
+    )
+    .await;
+  })
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+  command
+    .name(NAME)
+    .description("Configure how the left and right channels are played in this server")
+    .default_member_permissions(Permissions::MANAGE_GUILD)
+    .create_option(|option| {
+      option
+        .name("mono")
+        .description("Whether to play the same audio on both channels")
+        .kind(CommandOptionType::Boolean)
+    })
+    .create_option(|option| {
+      option
+        .name("balance")
+        .description("Shift the audio to the left (-100) or right (100), 0 is centered")
+        .kind(CommandOptionType::Integer)
+        .min_int_value(-100)
+        .max_int_value(100)
+    })
+}
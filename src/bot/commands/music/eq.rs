@@ -0,0 +1,149 @@
+use log::error;
+use serenity::{
+  builder::CreateApplicationCommand,
+  model::prelude::{
+    command::CommandOptionType, interaction::application_command::ApplicationCommandInteraction,
+  },
+  prelude::Context,
+};
+
+use crate::{
+  audio::eq::EqPreset,
+  bot::commands::{respond_message, CommandOutput},
+  database::Database,
+  session::manager::SessionManager,
+  utils::embed::{EmbedBuilder, Status},
+};
+
+pub const NAME: &str = "eq";
+
+pub fn command(ctx: Context, command: ApplicationCommandInteraction) -> CommandOutput {
+  Box::pin(async move {
+    let data = ctx.data.read().await;
+    let database = data.get::<Database>().expect("to contain a value");
+    let session_manager = data
+      .get::<SessionManager>()
+      .expect("to contain a value")
+      .clone();
+
+    let guild_id = command.guild_id.expect("to contain a value");
+
+    let session = match session_manager.get_session(guild_id).await {
+      Some(session) => session,
+      None => {
+        respond_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .title("Cannot change equalizer")
+            .icon_url("https://spoticord.com/forbidden.png")
+            .description("I'm currently not playing any music in this server")
+            .status(Status::Error)
+            .build(),
+          true,
+        )
+        .await;
+
+        return;
+      }
+    };
+
+    if session.owner().await != Some(command.user.id) {
+      respond_message(
+        &ctx,
+        &command,
+        EmbedBuilder::new()
+          .title("Cannot change equalizer")
+          .icon_url("https://spoticord.com/forbidden.png")
+          .description("You must be the host to change the equalizer")
+          .status(Status::Error)
+          .build(),
+        true,
+      )
+      .await;
+
+      return;
+    }
+
+    let preset = match command
+      .data
+      .options
+      .get(0)
+      .and_then(|option| option.value.as_ref())
+      .and_then(|value| value.as_str())
+      .and_then(|value| value.parse::<EqPreset>().ok())
+    {
+      Some(preset) => preset,
+      None => {
+        respond_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .description("You need to provide a valid equalizer preset.")
+            .status(Status::Error)
+            .build(),
+          true,
+        )
+        .await;
+
+        return;
+      }
+    };
+
+    session.set_eq_preset(preset).await;
+
+    // Persist the preset, so that it is used again the next time a player is created
+    if let Err(why) = database
+      .modify_guild_settings(guild_id.to_string(), |settings| settings.eq_preset = preset)
+      .await
+    {
+      error!("Failed to update guild settings: {:?}", why);
+
+      respond_message(
+        &ctx,
+        &command,
+        EmbedBuilder::new()
+          .description(format!(
+            "The equalizer is now set to **{}**, but it could not be saved for future sessions.",
+            preset.name()
+          ))
+          .status(Status::Warning)
+          .build(),
+        false,
+      )
+      .await;
+
+      return;
+    }
+
+    respond_message(
+      &ctx,
+      &command,
+      EmbedBuilder::new()
+        .description(format!("The equalizer is now set to **{}**", preset.name()))
+        .status(Status::Success)
+        .build(),
+      false,
+    )
+    .await;
+  })
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+  command
+    .name(NAME)
+    .description("Change the equalizer preset of the bot in this server")
+    .create_option(|option| {
+      option
+        .name("preset")
+        .description("The equalizer preset to use")
+        .kind(CommandOptionType::String)
+        .required(true);
+
+      for preset in EqPreset::ALL {
+        option.add_string_choice(preset.name(), preset.id());
+      }
+
+      option
+    })
+}
@@ -1,14 +1,23 @@
 use log::error;
 use serenity::{
   builder::CreateApplicationCommand,
-  model::prelude::{interaction::application_command::ApplicationCommandInteraction, Channel},
+  model::prelude::{
+    interaction::application_command::ApplicationCommandInteraction, Channel, UserId,
+  },
   prelude::Context,
 };
 
 use crate::{
   bot::commands::{defer_message, respond_message, update_message, CommandOutput},
-  session::manager::{SessionCreateError, SessionManager},
-  utils::embed::{EmbedBuilder, Status},
+  database::Database,
+  session::{
+    manager::{SessionCreateError, SessionManager},
+    SpoticordSession,
+  },
+  utils::{
+    embed::{EmbedBuilder, Status},
+    spotify,
+  },
 };
 
 pub const NAME: &str = "join";
@@ -168,6 +177,7 @@ pub fn command(ctx: Context, command: ApplicationCommandInteraction) -> CommandO
     }
 
     let data = ctx.data.read().await;
+    let database = data.get::<Database>().expect("to contain a value");
     let session_manager = data
       .get::<SessionManager>()
       .expect("to contain a value")
@@ -316,6 +326,11 @@ pub fn command(ctx: Context, command: ApplicationCommandInteraction) -> CommandO
       };
     }
 
+    let transferred = match session_manager.get_session(guild.id).await {
+      Some(session) => transfer_playback(database, &session, command.user.id).await,
+      None => false,
+    };
+
     update_message(
       &ctx,
       &command,
@@ -323,7 +338,11 @@ pub fn command(ctx: Context, command: ApplicationCommandInteraction) -> CommandO
         .title("Connected to voice channel")
         .icon_url("https://spoticord.com/speaker.png")
         .description(format!("Come listen along in <#{}>", channel_id))
-        .footer("You must manually go to Spotify and select your device")
+        .footer(if transferred {
+          "Your Spotify playback has been moved to Spoticord"
+        } else {
+          "You must manually go to Spotify and select your device"
+        })
         .status(Status::Info)
         .build(),
     )
@@ -331,6 +350,43 @@ pub fn command(ctx: Context, command: ApplicationCommandInteraction) -> CommandO
   })
 }
 
+/// Move the Spotify playback of the host to the device of the session, unless they opted out
+///
+/// Returns whether playback was moved.
+async fn transfer_playback(
+  database: &Database,
+  session: &SpoticordSession,
+  user_id: UserId,
+) -> bool {
+  match database.get_user(user_id.to_string()).await {
+    Ok(user) if !user.auto_transfer.unwrap_or(true) => return false,
+    Ok(_) => {}
+    Err(why) => {
+      error!("Failed to get user: {:?}", why);
+      return false;
+    }
+  }
+
+  let Some(device_id) = session.device_id().await else {
+    return false;
+  };
+
+  let token = match database.get_access_token(user_id.to_string()).await {
+    Ok(token) => token,
+    Err(why) => {
+      error!("Failed to get access token: {:?}", why);
+      return false;
+    }
+  };
+
+  if let Err(why) = spotify::transfer_playback(token, &device_id).await {
+    error!("Failed to transfer playback: {:?}", why);
+    return false;
+  }
+
+  true
+}
+
 pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
   command
     .name(NAME)
@@ -1,3 +1,14 @@
+pub mod bitrate;
+pub mod channels;
+pub mod eq;
 pub mod join;
 pub mod leave;
+pub mod mode;
+pub mod normalisation;
+pub mod play;
 pub mod playing;
+pub mod queue;
+pub mod search;
+pub mod seek;
+pub mod speed;
+pub mod volume;
@@ -0,0 +1,153 @@
+use serde_json::Value;
+use serenity::{
+  builder::CreateApplicationCommand,
+  model::prelude::{
+    command::CommandOptionType, interaction::application_command::ApplicationCommandInteraction,
+  },
+  prelude::Context,
+};
+
+use crate::{
+  bot::commands::{respond_message, CommandOutput},
+  session::manager::SessionManager,
+  utils::{
+    embed::{EmbedBuilder, Status},
+    spotify::RepeatMode,
+  },
+};
+
+pub const NAME: &str = "mode";
+
+pub fn command(ctx: Context, command: ApplicationCommandInteraction) -> CommandOutput {
+  Box::pin(async move {
+    let data = ctx.data.read().await;
+    let session_manager = data
+      .get::<SessionManager>()
+      .expect("to contain a value")
+      .clone();
+
+    let guild_id = command.guild_id.expect("to contain a value");
+
+    let session = match session_manager.get_session(guild_id).await {
+      Some(session) => session,
+      None => {
+        respond_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .title("Cannot change play mode")
+            .icon_url("https://spoticord.com/forbidden.png")
+            .description("I'm currently not playing any music in this server")
+            .status(Status::Error)
+            .build(),
+          true,
+        )
+        .await;
+
+        return;
+      }
+    };
+
+    if session.owner().await != Some(command.user.id) {
+      respond_message(
+        &ctx,
+        &command,
+        EmbedBuilder::new()
+          .title("Cannot change play mode")
+          .icon_url("https://spoticord.com/forbidden.png")
+          .description("You must be the host to change the play mode")
+          .status(Status::Error)
+          .build(),
+        true,
+      )
+      .await;
+
+      return;
+    }
+
+    let pbi = match session.playback_info().await {
+      Some(pbi) => pbi,
+      None => {
+        respond_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .title("Cannot change play mode")
+            .icon_url("https://spoticord.com/forbidden.png")
+            .description("I'm currently not playing any music in this server")
+            .status(Status::Error)
+            .build(),
+          true,
+        )
+        .await;
+
+        return;
+      }
+    };
+
+    let option = |name: &str| -> Option<&Value> {
+      command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| option.value.as_ref())
+    };
+
+    let shuffle = option("shuffle")
+      .and_then(|value| value.as_bool())
+      .unwrap_or(pbi.shuffle);
+    let repeat = option("repeat")
+      .and_then(|value| value.as_str())
+      .and_then(|value| value.parse::<RepeatMode>().ok())
+      .unwrap_or(pbi.repeat);
+
+    if shuffle != pbi.shuffle {
+      session.set_shuffle(shuffle).await;
+    }
+
+    if repeat != pbi.repeat {
+      session.set_repeat(repeat).await;
+    }
+
+    respond_message(
+      &ctx,
+      &command,
+      EmbedBuilder::new()
+        .title("Play mode")
+        .description(format!(
+          "Shuffle: **{}**\nRepeat: **{}**",
+          if shuffle { "On" } else { "Off" },
+          repeat.name()
+        ))
+        .status(Status::Info)
+        .build(),
+      false,
+    )
+    .await;
+  })
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+  command
+    .name(NAME)
+    .description("Change or show the shuffle and repeat mode")
+    .create_option(|option| {
+      option
+        .name("shuffle")
+        .description("Whether to play tracks in a random order")
+        .kind(CommandOptionType::Boolean)
+    })
+    .create_option(|option| {
+      option
+        .name("repeat")
+        .description("What to repeat")
+        .kind(CommandOptionType::String);
+
+      for mode in RepeatMode::ALL {
+        option.add_string_choice(mode.name(), mode.id());
+      }
+
+      option
+    })
+}
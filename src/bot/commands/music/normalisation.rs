@@ -0,0 +1,179 @@
+use log::error;
+use serde_json::Value;
+use serenity::{
+  builder::CreateApplicationCommand,
+  model::{
+    prelude::{
+      command::CommandOptionType, interaction::application_command::ApplicationCommandInteraction,
+    },
+    Permissions,
+  },
+  prelude::Context,
+};
+
+use crate::{
+  bot::commands::{respond_message, CommandOutput},
+  database::{Database, NormalisationKind, NormalisationSettings},
+  utils::embed::{EmbedBuilder, Status},
+};
+
+pub const NAME: &str = "normalisation";
+
+pub fn command(ctx: Context, command: ApplicationCommandInteraction) -> CommandOutput {
+  Box::pin(async move {
+    let data = ctx.data.read().await;
+    let database = data.get::<Database>().expect("to contain a value");
+
+    let guild_id = command.guild_id.expect("to contain a value");
+
+    let option = |name: &str| -> Option<&Value> {
+      command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| option.value.as_ref())
+    };
+
+    let enabled = option("enabled").and_then(|value| value.as_bool());
+    let kind = option("type").and_then(|value| match value.as_str() {
+      Some("track") => Some(NormalisationKind::Track),
+      Some("album") => Some(NormalisationKind::Album),
+      Some("auto") => Some(NormalisationKind::Auto),
+      _ => None,
+    });
+    let pregain = option("pregain").and_then(|value| value.as_f64());
+    let threshold = option("threshold").and_then(|value| value.as_f64());
+    let limiter = option("limiter").and_then(|value| value.as_bool());
+
+    let result = database
+      .modify_guild_settings(guild_id.to_string(), |settings| {
+        let normalisation = &mut settings.normalisation;
+
+        if let Some(enabled) = enabled {
+          normalisation.enabled = enabled;
+        }
+
+        if let Some(kind) = kind {
+          normalisation.kind = kind;
+        }
+
+        if let Some(pregain) = pregain {
+          normalisation.pregain_db = pregain;
+        }
+
+        if let Some(threshold) = threshold {
+          normalisation.threshold_dbfs = threshold;
+        }
+
+        if let Some(limiter) = limiter {
+          normalisation.limiter = limiter;
+        }
+      })
+      .await;
+
+    if let Err(why) = result {
+      error!("Failed to update guild settings: {:?}", why);
+
+      respond_message(
+        &ctx,
+        &command,
+        EmbedBuilder::new()
+          .description("Something went wrong while trying to save the normalisation settings.")
+          .status(Status::Error)
+          .build(),
+        true,
+      )
+      .await;
+
+      return;
+    }
+
+    let settings = match database.get_guild_settings(guild_id.to_string()).await {
+      Ok(settings) => settings.normalisation,
+      Err(why) => {
+        error!("Failed to get guild settings: {:?}", why);
+        NormalisationSettings::default()
+      }
+    };
+
+    respond_message(
+      &ctx,
+      &command,
+      EmbedBuilder::new()
+        .title("Loudness normalisation")
+        .description(format!(
+          "{}\n\nChanges are applied the next time someone starts playing music.",
+          describe(&settings)
+        ))
+        .status(Status::Info)
+        .build(),
+      true,
+    )
+    .await;
+  })
+}
+
+fn describe(settings: &NormalisationSettings) -> String {
+  if !settings.enabled {
+    return "Normalisation is **disabled**".into();
+  }
+
+  let kind = match settings.kind {
+    NormalisationKind::Track => "Track",
+    NormalisationKind::Album => "Album",
+    NormalisationKind::Auto => "Auto",
+  };
+
+  format!(
+    "Normalisation is **enabled**\nType: **{}**\nPregain: **{:.1} dB**\nThreshold: **{:.1} dBFS**\nLimiter: **{}**",
+    kind,
+    settings.pregain_db,
+    settings.threshold_dbfs,
+    if settings.limiter { "on" } else { "off" }
+  )
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+  command
+    .name(NAME)
+    .description("Configure the loudness normalisation of the bot in this server")
+    .default_member_permissions(Permissions::MANAGE_GUILD)
+    .create_option(|option| {
+      option
+        .name("enabled")
+        .description("Whether to even out the volume between tracks")
+        .kind(CommandOptionType::Boolean)
+    })
+    .create_option(|option| {
+      option
+        .name("type")
+        .description("Which loudness information to normalise with")
+        .kind(CommandOptionType::String)
+        .add_string_choice("Track", "track")
+        .add_string_choice("Album", "album")
+        .add_string_choice("Auto", "auto")
+    })
+    .create_option(|option| {
+      option
+        .name("pregain")
+        .description("Extra gain applied to every track, in dB")
+        .kind(CommandOptionType::Number)
+        .min_number_value(-10.0)
+        .max_number_value(10.0)
+    })
+    .create_option(|option| {
+      option
+        .name("threshold")
+        .description("The level above which the limiter kicks in, in dBFS")
+        .kind(CommandOptionType::Number)
+        .min_number_value(-10.0)
+        .max_number_value(0.0)
+    })
+    .create_option(|option| {
+      option
+        .name("limiter")
+        .description("Whether to use a dynamic limiter to prevent clipping")
+        .kind(CommandOptionType::Boolean)
+    })
+}
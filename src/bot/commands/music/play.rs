@@ -0,0 +1,166 @@
+use log::error;
+use serenity::{
+  builder::CreateApplicationCommand,
+  model::prelude::{
+    command::CommandOptionType, interaction::application_command::ApplicationCommandInteraction,
+  },
+  prelude::Context,
+};
+
+use crate::{
+  bot::commands::{defer_message, respond_message, update_message, CommandOutput},
+  database::Database,
+  session::{manager::SessionManager, SpoticordSession},
+  utils::{
+    embed::{EmbedBuilder, Status},
+    spotify::{self, SpotifyItem},
+  },
+};
+
+pub const NAME: &str = "play";
+
+pub fn command(ctx: Context, command: ApplicationCommandInteraction) -> CommandOutput {
+  Box::pin(async move {
+    let data = ctx.data.read().await;
+    let database = data.get::<Database>().expect("to contain a value");
+    let session_manager = data
+      .get::<SessionManager>()
+      .expect("to contain a value")
+      .clone();
+
+    let guild_id = command.guild_id.expect("to contain a value");
+
+    let session = match session_manager.get_session(guild_id).await {
+      Some(session) => session,
+      None => {
+        respond_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .title("Cannot start playback")
+            .icon_url("https://spoticord.com/forbidden.png")
+            .description("I'm currently not connected to a voice channel in this server. Use </join:1036714850367320142> first.")
+            .status(Status::Error)
+            .build(),
+          true,
+        )
+        .await;
+
+        return;
+      }
+    };
+
+    if session.owner().await != Some(command.user.id) {
+      respond_message(
+        &ctx,
+        &command,
+        EmbedBuilder::new()
+          .title("Cannot start playback")
+          .icon_url("https://spoticord.com/forbidden.png")
+          .description("You must be the host to start playback")
+          .status(Status::Error)
+          .build(),
+        true,
+      )
+      .await;
+
+      return;
+    }
+
+    let item = match command
+      .data
+      .options
+      .get(0)
+      .and_then(|option| option.value.as_ref())
+      .and_then(|value| value.as_str())
+      .map(|value| value.parse::<SpotifyItem>())
+    {
+      Some(Ok(item)) => item,
+      _ => {
+        respond_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .description("You need to provide a Spotify link or URI of a track, album, playlist, episode or show.")
+            .status(Status::Error)
+            .build(),
+          true,
+        )
+        .await;
+
+        return;
+      }
+    };
+
+    defer_message(&ctx, &command, false).await;
+
+    if let Err(why) = start_item(database, &session, &item).await {
+      update_message(
+        &ctx,
+        &command,
+        EmbedBuilder::new()
+          .title("Cannot start playback")
+          .description(why)
+          .status(Status::Error)
+          .build(),
+      )
+      .await;
+
+      return;
+    }
+
+    update_message(
+      &ctx,
+      &command,
+      EmbedBuilder::new()
+        .title("Started playback")
+        .icon_url("https://spoticord.com/spotify-logo.png")
+        .description(format!(
+          "Now playing the {} you requested",
+          item.kind.as_str()
+        ))
+        .status(Status::Success)
+        .build(),
+    )
+    .await;
+  })
+}
+
+/// Start playing an item on the device of a session, using the Spotify account of its host
+///
+/// On failure, a description of the problem that can be shown to the user is returned.
+pub async fn start_item(
+  database: &Database,
+  session: &SpoticordSession,
+  item: &SpotifyItem,
+) -> Result<(), String> {
+  let (Some(owner), Some(device_id)) = (session.owner().await, session.device_id().await) else {
+    return Err("The player is not ready yet, please try again in a few seconds.".into());
+  };
+
+  let token = match database.get_access_token(owner.to_string()).await {
+    Ok(token) => token,
+    Err(why) => {
+      error!("Failed to get access token: {:?}", why);
+
+      return Err("Spoticord could not access the Spotify account of the host. Use </link:1036714850367320136> to relink your account if this keeps happening.".into());
+    }
+  };
+
+  spotify::start_playback(token, &device_id, item)
+    .await
+    .map_err(|why| format!("Error details: `{why}`"))
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+  command
+    .name(NAME)
+    .description("Play a track, album, playlist, episode or show from Spotify")
+    .create_option(|option| {
+      option
+        .name("uri")
+        .description("The Spotify link or URI to play")
+        .kind(CommandOptionType::String)
+        .required(true)
+    })
+}
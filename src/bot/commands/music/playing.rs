@@ -19,15 +19,23 @@ use serenity::{
 
 use crate::{
   bot::commands::{respond_component_message, respond_message, CommandOutput},
+  database::Database,
   session::{manager::SessionManager, pbi::PlaybackInfo},
   utils::{
     self,
     embed::{EmbedBuilder, Status},
+    spotify::RepeatMode,
   },
 };
 
 pub const NAME: &str = "playing";
 
+/// The amount (in percent) the volume buttons change the volume by
+const VOLUME_STEP: u8 = 10;
+
+/// How far (in milliseconds) the seek buttons skip
+const SEEK_STEP: u32 = 15_000;
+
 pub fn command(ctx: Context, command: ApplicationCommandInteraction) -> CommandOutput {
   Box::pin(async move {
     let not_playing = async {
@@ -110,7 +118,7 @@ pub fn command(ctx: Context, command: ApplicationCommandInteraction) -> CommandO
     };
 
     // Get metadata
-    let (title, description, thumbnail) = get_metadata(&pbi);
+    let (title, description, thumbnail) = get_metadata(&pbi, session.volume().await);
 
     if let Err(why) = command
       .create_interaction_response(&ctx.http, |response| {
@@ -126,7 +134,7 @@ pub fn command(ctx: Context, command: ApplicationCommandInteraction) -> CommandO
                 owner,
                 thumbnail,
               ))
-              .components(|components| create_button(components, pbi.is_playing))
+              .components(|components| create_button(components, &pbi))
           })
       })
       .await
@@ -284,6 +292,47 @@ pub fn component(ctx: Context, mut interaction: MessageComponentInteraction) ->
 
       "playing::btn_next_track" => session.next().await,
 
+      "playing::btn_volume_down" | "playing::btn_volume_up" => {
+        let volume = session.volume().await.unwrap_or_default();
+        let volume = if interaction.data.custom_id == "playing::btn_volume_up" {
+          volume.saturating_add(VOLUME_STEP).min(100)
+        } else {
+          volume.saturating_sub(VOLUME_STEP)
+        };
+
+        session.set_volume(volume).await;
+
+        let database = data.get::<Database>().expect("to contain a value");
+        if let Err(why) = database
+          .update_user_volume(interaction.user.id.to_string(), volume)
+          .await
+        {
+          error!("Failed to update user volume: {:?}", why);
+        }
+      }
+
+      "playing::btn_shuffle" => session.set_shuffle(!pbi.shuffle).await,
+
+      "playing::btn_repeat" => session.set_repeat(pbi.repeat.next()).await,
+
+      "playing::btn_seek_backward" => {
+        session
+          .seek(pbi.get_position().saturating_sub(SEEK_STEP))
+          .await
+      }
+
+      "playing::btn_seek_forward" => {
+        let position_ms = pbi.get_position() + SEEK_STEP;
+
+        // The duration is unknown when it is zero, so there is nothing to clamp to
+        session
+          .seek(match pbi.duration_ms {
+            0 => position_ms,
+            duration_ms => position_ms.min(duration_ms),
+          })
+          .await
+      }
+
       _ => {
         error!("Unknown custom_id: {}", interaction.data.custom_id);
       }
@@ -291,10 +340,15 @@ pub fn component(ctx: Context, mut interaction: MessageComponentInteraction) ->
 
     interaction.defer(&ctx.http).await.ok();
     tokio::time::sleep(Duration::from_millis(
-      if interaction.data.custom_id == "playing::btn_pause_play" {
-        0
-      } else {
-        2500
+      match interaction.data.custom_id.as_str() {
+        "playing::btn_pause_play"
+        | "playing::btn_volume_down"
+        | "playing::btn_volume_up"
+        | "playing::btn_seek_backward"
+        | "playing::btn_seek_forward"
+        | "playing::btn_shuffle"
+        | "playing::btn_repeat" => 0,
+        _ => 2500,
       },
     ))
     .await;
@@ -308,7 +362,10 @@ pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicatio
     .description("Display which song is currently being played")
 }
 
-fn create_button(components: &mut CreateComponents, playing: bool) -> &mut CreateComponents {
+fn create_button<'a>(
+  components: &'a mut CreateComponents,
+  pbi: &PlaybackInfo,
+) -> &'a mut CreateComponents {
   let mut prev_btn = CreateButton::default();
   prev_btn
     .style(ButtonStyle::Primary)
@@ -318,7 +375,7 @@ fn create_button(components: &mut CreateComponents, playing: bool) -> &mut Creat
   let mut toggle_btn = CreateButton::default();
   toggle_btn
     .style(ButtonStyle::Secondary)
-    .label(if playing { "Pause" } else { "Play" })
+    .label(if pbi.is_playing { "Pause" } else { "Play" })
     .custom_id("playing::btn_pause_play");
 
   let mut next_btn = CreateButton::default();
@@ -327,11 +384,63 @@ fn create_button(components: &mut CreateComponents, playing: bool) -> &mut Creat
     .label(">>")
     .custom_id("playing::btn_next_track");
 
-  components.create_action_row(|ar| {
-    ar.add_button(prev_btn)
-      .add_button(toggle_btn)
-      .add_button(next_btn)
-  })
+  let mut volume_down_btn = CreateButton::default();
+  volume_down_btn
+    .style(ButtonStyle::Secondary)
+    .label("Vol -")
+    .custom_id("playing::btn_volume_down");
+
+  let mut volume_up_btn = CreateButton::default();
+  volume_up_btn
+    .style(ButtonStyle::Secondary)
+    .label("Vol +")
+    .custom_id("playing::btn_volume_up");
+
+  let mut seek_backward_btn = CreateButton::default();
+  seek_backward_btn
+    .style(ButtonStyle::Secondary)
+    .label(format!("-{}s", SEEK_STEP / 1000))
+    .custom_id("playing::btn_seek_backward");
+
+  let mut seek_forward_btn = CreateButton::default();
+  seek_forward_btn
+    .style(ButtonStyle::Secondary)
+    .label(format!("+{}s", SEEK_STEP / 1000))
+    .custom_id("playing::btn_seek_forward");
+
+  let mut shuffle_btn = CreateButton::default();
+  shuffle_btn
+    .style(if pbi.shuffle {
+      ButtonStyle::Success
+    } else {
+      ButtonStyle::Secondary
+    })
+    .label("Shuffle")
+    .custom_id("playing::btn_shuffle");
+
+  let mut repeat_btn = CreateButton::default();
+  repeat_btn
+    .style(match pbi.repeat {
+      RepeatMode::Off => ButtonStyle::Secondary,
+      _ => ButtonStyle::Success,
+    })
+    .label(format!("Repeat: {}", pbi.repeat.name()))
+    .custom_id("playing::btn_repeat");
+
+  components
+    .create_action_row(|ar| {
+      ar.add_button(volume_down_btn)
+        .add_button(prev_btn)
+        .add_button(toggle_btn)
+        .add_button(next_btn)
+        .add_button(volume_up_btn)
+    })
+    .create_action_row(|ar| {
+      ar.add_button(seek_backward_btn)
+        .add_button(shuffle_btn)
+        .add_button(repeat_btn)
+        .add_button(seek_forward_btn)
+    })
 }
 
 async fn update_embed(interaction: &mut MessageComponentInteraction, ctx: &Context, owner: User) {
@@ -400,7 +509,7 @@ async fn update_embed(interaction: &mut MessageComponentInteraction, ctx: &Conte
     }
   };
 
-  let (title, description, thumbnail) = get_metadata(&pbi);
+  let (title, description, thumbnail) = get_metadata(&pbi, session.volume().await);
 
   if let Err(why) = interaction
     .message
@@ -414,7 +523,7 @@ async fn update_embed(interaction: &mut MessageComponentInteraction, ctx: &Conte
           owner,
           thumbnail,
         ))
-        .components(|components| create_button(components, pbi.is_playing));
+        .components(|components| create_button(components, &pbi));
 
       message
     })
@@ -455,15 +564,22 @@ fn build_playing_embed(
   embed
 }
 
-fn get_metadata(pbi: &PlaybackInfo) -> (String, String, String) {
+fn get_metadata(pbi: &PlaybackInfo, volume: Option<u8>) -> (String, String, String) {
   // Create title
   let title = format!("{} - {}", pbi.get_artists(), pbi.get_name());
 
   // Create description
   let mut description = String::new();
 
-  let position = pbi.get_position();
-  let spot = position * 20 / pbi.duration_ms;
+  // Local files and some unavailable tracks don't report a duration
+  let position = match pbi.duration_ms {
+    0 => pbi.get_position(),
+    duration_ms => pbi.get_position().min(duration_ms),
+  };
+  let spot = match pbi.duration_ms {
+    0 => 0,
+    duration_ms => (position as u64 * 20 / duration_ms as u64).min(19) as u32,
+  };
 
   description.push_str(if pbi.is_playing { "▶️ " } else { "⏸️ " });
 
@@ -476,14 +592,30 @@ fn get_metadata(pbi: &PlaybackInfo) -> (String, String, String) {
   }
 
   description.push_str("\n:alarm_clock: ");
+  if pbi.duration_ms > 0 {
+    description.push_str(&format!(
+      "{} / {}",
+      utils::time_to_str(position / 1000),
+      utils::time_to_str(pbi.duration_ms / 1000)
+    ));
+  } else {
+    description.push_str(&utils::time_to_str(position / 1000));
+  }
+
+  if let Some(volume) = volume {
+    description.push_str(&format!("\n:loud_sound: {}%", volume));
+  }
+
   description.push_str(&format!(
-    "{} / {}",
-    utils::time_to_str(position / 1000),
-    utils::time_to_str(pbi.duration_ms / 1000)
+    "\n:twisted_rightwards_arrows: {}  :repeat: {}",
+    if pbi.shuffle { "On" } else { "Off" },
+    pbi.repeat.name()
   ));
 
-  // Get the thumbnail image
-  let thumbnail = pbi.get_thumbnail_url().expect("to contain a value");
+  // Get the thumbnail image, not everything has artwork
+  let thumbnail = pbi
+    .get_thumbnail_url()
+    .unwrap_or_else(|| "https://spoticord.com/spotify-logo.png".to_string());
 
   (title, description, thumbnail)
 }
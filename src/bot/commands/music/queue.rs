@@ -0,0 +1,272 @@
+use log::error;
+use serenity::{
+  builder::{CreateApplicationCommand, CreateButton, CreateComponents},
+  model::prelude::{
+    component::ButtonStyle,
+    interaction::{
+      application_command::ApplicationCommandInteraction,
+      message_component::MessageComponentInteraction,
+    },
+  },
+  prelude::Context,
+};
+
+use crate::{
+  bot::commands::{defer_message, respond_message, CommandOutput},
+  database::Database,
+  session::{manager::SessionManager, pbi::CurrentTrack, SpoticordSession},
+  utils::{
+    self,
+    embed::{make_embed_message, EmbedBuilder, EmbedMessageOptions, Status},
+    spotify,
+  },
+};
+
+pub const NAME: &str = "queue";
+
+/// The amount of tracks shown on a single page
+const PAGE_SIZE: usize = 10;
+
+pub fn command(ctx: Context, command: ApplicationCommandInteraction) -> CommandOutput {
+  Box::pin(async move {
+    let data = ctx.data.read().await;
+    let database = data.get::<Database>().expect("to contain a value");
+    let session_manager = data
+      .get::<SessionManager>()
+      .expect("to contain a value")
+      .clone();
+
+    let guild_id = command.guild_id.expect("to contain a value");
+
+    let session = match session_manager.get_session(guild_id).await {
+      Some(session) => session,
+      None => {
+        respond_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .title("Cannot get queue")
+            .icon_url("https://spoticord.com/forbidden.png")
+            .description("I'm currently not playing any music in this server")
+            .status(Status::Error)
+            .build(),
+          true,
+        )
+        .await;
+
+        return;
+      }
+    };
+
+    defer_message(&ctx, &command, false).await;
+
+    let (embed, pages) = queue_page(database, &session, 0).await;
+
+    if let Err(why) = command
+      .edit_original_interaction_response(&ctx.http, |message| {
+        message
+          .embed(|e| make_embed_message(e, embed))
+          .components(|components| create_buttons(components, 0, pages))
+      })
+      .await
+    {
+      error!("Error sending message: {:?}", why);
+    }
+  })
+}
+
+pub fn component(ctx: Context, interaction: MessageComponentInteraction) -> CommandOutput {
+  Box::pin(async move {
+    let data = ctx.data.read().await;
+    let database = data.get::<Database>().expect("to contain a value");
+    let session_manager = data
+      .get::<SessionManager>()
+      .expect("to contain a value")
+      .clone();
+
+    let page = match interaction
+      .data
+      .custom_id
+      .strip_prefix("queue::page::")
+      .and_then(|page| page.parse::<usize>().ok())
+    {
+      Some(page) => page,
+      None => return,
+    };
+
+    interaction.defer(&ctx.http).await.ok();
+
+    let (embed, pages) = match session_manager
+      .get_session(interaction.guild_id.expect("to contain a value"))
+      .await
+    {
+      Some(session) => queue_page(database, &session, page).await,
+      None => (
+        EmbedBuilder::new()
+          .title("Cannot get queue")
+          .icon_url("https://spoticord.com/forbidden.png")
+          .description("I'm currently not playing any music in this server")
+          .status(Status::Error)
+          .build(),
+        0,
+      ),
+    };
+
+    if let Err(why) = interaction
+      .edit_original_interaction_response(&ctx.http, |response| {
+        response
+          .embed(|e| make_embed_message(e, embed))
+          .components(|components| create_buttons(components, page, pages))
+      })
+      .await
+    {
+      error!("Failed to update queue: {:?}", why);
+    }
+  })
+}
+
+/// Build the message showing a page of the upcoming tracks, together with the amount of pages
+///
+/// Spotify only reports the next 20 or so items, so a longer queue is cut off.
+async fn queue_page(
+  database: &Database,
+  session: &SpoticordSession,
+  page: usize,
+) -> (EmbedMessageOptions, usize) {
+  let Some(owner) = session.owner().await else {
+    return (
+      EmbedBuilder::new()
+        .title("Cannot get queue")
+        .icon_url("https://spoticord.com/forbidden.png")
+        .description("I'm currently not playing any music in this server")
+        .status(Status::Error)
+        .build(),
+      0,
+    );
+  };
+
+  let queue = match database.get_access_token(owner.to_string()).await {
+    Ok(token) => spotify::get_queue(token).await,
+    Err(why) => {
+      error!("Failed to get access token: {:?}", why);
+
+      return (
+        EmbedBuilder::new()
+          .title("Cannot get queue")
+          .description("Spoticord could not access the Spotify account of the host. Use </link:1036714850367320136> to relink your account if this keeps happening.")
+          .status(Status::Error)
+          .build(),
+        0,
+      );
+    }
+  };
+
+  let queue = match queue {
+    Ok(queue) if !queue.is_empty() => queue,
+    Ok(_) => {
+      return (
+        EmbedBuilder::new()
+          .title("Up next")
+          .icon_url("https://spoticord.com/spotify-logo.png")
+          .description("There is nothing queued after the current track")
+          .status(Status::Info)
+          .build(),
+        0,
+      );
+    }
+    Err(why) => {
+      error!("Failed to get queue: {:?}", why);
+
+      return (
+        EmbedBuilder::new()
+          .title("Cannot get queue")
+          .description(format!("Error details: `{why}`"))
+          .status(Status::Error)
+          .build(),
+        0,
+      );
+    }
+  };
+
+  let pages = (queue.len() + PAGE_SIZE - 1) / PAGE_SIZE;
+  let page = page.min(pages - 1);
+  let start = page * PAGE_SIZE;
+  let end = (start + PAGE_SIZE).min(queue.len());
+
+  // Only the metadata of the tracks on this page is requested
+  let tracks = session.resolve(&queue[start..end]).await;
+
+  if tracks.is_empty() {
+    return (
+      EmbedBuilder::new()
+        .title("Cannot get queue")
+        .description("The upcoming tracks could not be loaded, please try again later")
+        .status(Status::Error)
+        .build(),
+      0,
+    );
+  }
+
+  let description = tracks
+    .iter()
+    .enumerate()
+    .map(|(index, track)| match track {
+      // Keep a row for tracks that failed to load, so the numbers still match the queue
+      CurrentTrack::Unknown => format!("`{}.` {}", start + index + 1, track.name()),
+      track => format!(
+        "`{}.` **{}** - {} ({})",
+        start + index + 1,
+        track.name(),
+        track.artists(),
+        utils::time_to_str(track.duration_ms() / 1000)
+      ),
+    })
+    .collect::<Vec<_>>()
+    .join("\n");
+
+  (
+    EmbedBuilder::new()
+      .title("Up next")
+      .icon_url("https://spoticord.com/spotify-logo.png")
+      .description(description)
+      .footer(format!("Page {} of {}", page + 1, pages))
+      .status(Status::Info)
+      .build(),
+    pages,
+  )
+}
+
+/// Add buttons to move between pages, if there's more than one
+fn create_buttons(
+  components: &mut CreateComponents,
+  page: usize,
+  pages: usize,
+) -> &mut CreateComponents {
+  if pages <= 1 {
+    return components;
+  }
+
+  let page = page.min(pages - 1);
+
+  let mut previous_btn = CreateButton::default();
+  previous_btn
+    .style(ButtonStyle::Secondary)
+    .label("Previous")
+    .custom_id(format!("queue::page::{}", page.saturating_sub(1)))
+    .disabled(page == 0);
+
+  let mut next_btn = CreateButton::default();
+  next_btn
+    .style(ButtonStyle::Secondary)
+    .label("Next")
+    .custom_id(format!("queue::page::{}", page + 1))
+    .disabled(page + 1 >= pages);
+
+  components.create_action_row(|ar| ar.add_button(previous_btn).add_button(next_btn))
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+  command
+    .name(NAME)
+    .description("Show the tracks that will be played next")
+}
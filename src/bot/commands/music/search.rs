@@ -0,0 +1,396 @@
+use log::error;
+use serenity::{
+  builder::{CreateApplicationCommand, CreateSelectMenuOption},
+  model::prelude::{
+    command::CommandOptionType,
+    interaction::{
+      application_command::ApplicationCommandInteraction, autocomplete::AutocompleteInteraction,
+      message_component::MessageComponentInteraction,
+    },
+  },
+  prelude::Context,
+};
+
+use super::play::start_item;
+use crate::{
+  bot::commands::{
+    defer_message, respond_component_message, respond_message, update_message, CommandOutput,
+  },
+  database::Database,
+  session::manager::SessionManager,
+  utils::{
+    embed::{make_embed_message, EmbedBuilder, EmbedMessageOptions, Status},
+    spotify::{self, SearchResult, SpotifyItem},
+  },
+};
+
+pub const NAME: &str = "search";
+
+/// The amount of results of every kind shown in the select menu
+const SEARCH_LIMIT: usize = 5;
+
+/// The amount of results of every kind suggested while typing, Discord shows up to 25 in total
+const AUTOCOMPLETE_LIMIT: usize = 3;
+
+/// Discord does not allow longer names and labels in choices and select menus
+const MAX_LABEL_LENGTH: usize = 100;
+
+pub fn command(ctx: Context, command: ApplicationCommandInteraction) -> CommandOutput {
+  Box::pin(async move {
+    let data = ctx.data.read().await;
+    let database = data.get::<Database>().expect("to contain a value");
+    let session_manager = data
+      .get::<SessionManager>()
+      .expect("to contain a value")
+      .clone();
+
+    let guild_id = command.guild_id.expect("to contain a value");
+
+    let session = match session_manager.get_session(guild_id).await {
+      Some(session) => session,
+      None => {
+        respond_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .title("Cannot search")
+            .icon_url("https://spoticord.com/forbidden.png")
+            .description("I'm currently not connected to a voice channel in this server. Use </join:1036714850367320142> first.")
+            .status(Status::Error)
+            .build(),
+          true,
+        )
+        .await;
+
+        return;
+      }
+    };
+
+    if session.owner().await != Some(command.user.id) {
+      respond_message(
+        &ctx,
+        &command,
+        EmbedBuilder::new()
+          .title("Cannot search")
+          .icon_url("https://spoticord.com/forbidden.png")
+          .description("You must be the host to pick what is played")
+          .status(Status::Error)
+          .build(),
+        true,
+      )
+      .await;
+
+      return;
+    }
+
+    let query = match command
+      .data
+      .options
+      .get(0)
+      .and_then(|option| option.value.as_ref())
+      .and_then(|value| value.as_str())
+    {
+      Some(query) if !query.trim().is_empty() => query.trim().to_string(),
+      _ => {
+        respond_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .description("You need to provide something to search for.")
+            .status(Status::Error)
+            .build(),
+          true,
+        )
+        .await;
+
+        return;
+      }
+    };
+
+    // Picking a suggestion fills in its URI, which can be played right away
+    if let Ok(item) = query.parse::<SpotifyItem>() {
+      defer_message(&ctx, &command, false).await;
+
+      let result = start_item(database, &session, &item).await;
+      update_message(&ctx, &command, playback_message(&item, result)).await;
+
+      return;
+    }
+
+    defer_message(&ctx, &command, true).await;
+
+    let results = match database.get_access_token(command.user.id.to_string()).await {
+      Ok(token) => spotify::search(token, &query, SEARCH_LIMIT).await,
+      Err(why) => {
+        error!("Failed to get access token: {:?}", why);
+
+        update_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .title("Cannot search")
+            .description("Spoticord could not access your Spotify account. Use </link:1036714850367320136> to relink your account if this keeps happening.")
+            .status(Status::Error)
+            .build(),
+        )
+        .await;
+
+        return;
+      }
+    };
+
+    let results = match results {
+      Ok(results) if !results.is_empty() => results,
+      Ok(_) => {
+        update_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .description(format!("Nothing was found for **{query}**"))
+            .status(Status::Warning)
+            .build(),
+        )
+        .await;
+
+        return;
+      }
+      Err(why) => {
+        update_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .title("Cannot search")
+            .description(format!("Error details: `{why}`"))
+            .status(Status::Error)
+            .build(),
+        )
+        .await;
+
+        return;
+      }
+    };
+
+    let options = results
+      .iter()
+      .map(|result| {
+        let mut option = CreateSelectMenuOption::new(
+          truncate(&result.name, MAX_LABEL_LENGTH),
+          result.item.to_uri(),
+        );
+
+        option.description(truncate(
+          &format!("{} · {}", result.item.kind.name(), result.creator),
+          MAX_LABEL_LENGTH,
+        ));
+
+        option
+      })
+      .collect::<Vec<_>>();
+
+    let embed = EmbedBuilder::new()
+      .title("Search results")
+      .icon_url("https://spoticord.com/spotify-logo.png")
+      .description(format!(
+        "Pick what to play from the results for **{query}**"
+      ))
+      .status(Status::Info)
+      .build();
+
+    if let Err(why) = command
+      .edit_original_interaction_response(&ctx.http, |message| {
+        message
+          .embed(|e| make_embed_message(e, embed))
+          .components(|components| {
+            components.create_action_row(|row| {
+              row.create_select_menu(|menu| {
+                menu
+                  .custom_id("search::select")
+                  .placeholder("Pick a track, album or playlist")
+                  .options(|menu_options| menu_options.set_options(options))
+              })
+            })
+          })
+      })
+      .await
+    {
+      error!("Error sending message: {:?}", why);
+    }
+  })
+}
+
+pub fn component(ctx: Context, interaction: MessageComponentInteraction) -> CommandOutput {
+  Box::pin(async move {
+    let data = ctx.data.read().await;
+    let database = data.get::<Database>().expect("to contain a value");
+    let session_manager = data
+      .get::<SessionManager>()
+      .expect("to contain a value")
+      .clone();
+
+    let session = match session_manager
+      .get_session(interaction.guild_id.expect("to contain a value"))
+      .await
+    {
+      Some(session) => session,
+      None => {
+        respond_component_message(
+          &ctx,
+          &interaction,
+          EmbedBuilder::new()
+            .title("Cannot start playback")
+            .icon_url("https://spoticord.com/forbidden.png")
+            .description("I'm currently not connected to a voice channel in this server")
+            .status(Status::Error)
+            .build(),
+          true,
+        )
+        .await;
+
+        return;
+      }
+    };
+
+    if session.owner().await != Some(interaction.user.id) {
+      respond_component_message(
+        &ctx,
+        &interaction,
+        EmbedBuilder::new()
+          .title("Cannot start playback")
+          .icon_url("https://spoticord.com/forbidden.png")
+          .description("You must be the host to pick what is played")
+          .status(Status::Error)
+          .build(),
+        true,
+      )
+      .await;
+
+      return;
+    }
+
+    let item = match interaction
+      .data
+      .values
+      .get(0)
+      .and_then(|value| value.parse::<SpotifyItem>().ok())
+    {
+      Some(item) => item,
+      None => return,
+    };
+
+    interaction.defer(&ctx.http).await.ok();
+
+    let message = playback_message(&item, start_item(database, &session, &item).await);
+
+    // Replace the results, so that the same menu can't be used twice
+    if let Err(why) = interaction
+      .edit_original_interaction_response(&ctx.http, |response| {
+        response
+          .embed(|e| make_embed_message(e, message))
+          .components(|components| components)
+      })
+      .await
+    {
+      error!("Failed to update search results: {:?}", why);
+    }
+  })
+}
+
+pub fn autocomplete(ctx: Context, interaction: AutocompleteInteraction) -> CommandOutput {
+  Box::pin(async move {
+    let query = interaction
+      .data
+      .options
+      .iter()
+      .find(|option| option.focused)
+      .and_then(|option| option.value.as_ref())
+      .and_then(|value| value.as_str())
+      .unwrap_or_default()
+      .trim();
+
+    let mut results: Vec<SearchResult> = vec![];
+
+    // Don't search for single letters, or for suggestions that were already picked
+    if query.chars().count() >= 2 && query.parse::<SpotifyItem>().is_err() {
+      let data = ctx.data.read().await;
+      let database = data.get::<Database>().expect("to contain a value");
+
+      // Users that haven't linked their account simply get no suggestions
+      if let Ok(token) = database
+        .get_access_token(interaction.user.id.to_string())
+        .await
+      {
+        match spotify::search(token, query, AUTOCOMPLETE_LIMIT).await {
+          Ok(found) => results = found,
+          Err(why) => error!("Failed to search: {:?}", why),
+        }
+      }
+    }
+
+    if let Err(why) = interaction
+      .create_autocomplete_response(&ctx.http, |response| {
+        for result in &results {
+          response.add_string_choice(
+            truncate(
+              &format!(
+                "{}: {} - {}",
+                result.item.kind.name(),
+                result.name,
+                result.creator
+              ),
+              MAX_LABEL_LENGTH,
+            ),
+            result.item.to_uri(),
+          );
+        }
+
+        response
+      })
+      .await
+    {
+      error!("Failed to send autocomplete results: {:?}", why);
+    }
+  })
+}
+
+/// The message shown after trying to start playback of an item
+fn playback_message(item: &SpotifyItem, result: Result<(), String>) -> EmbedMessageOptions {
+  match result {
+    Ok(()) => EmbedBuilder::new()
+      .title("Started playback")
+      .icon_url("https://spoticord.com/spotify-logo.png")
+      .description(format!("Now playing the {} you picked", item.kind.as_str()))
+      .status(Status::Success)
+      .build(),
+    Err(why) => EmbedBuilder::new()
+      .title("Cannot start playback")
+      .description(why)
+      .status(Status::Error)
+      .build(),
+  }
+}
+
+/// Shorten text to at most `max` characters
+fn truncate(text: &str, max: usize) -> String {
+  if text.chars().count() <= max {
+    return text.to_string();
+  }
+
+  let mut truncated = text.chars().take(max - 1).collect::<String>();
+  truncated.push('…');
+
+  truncated
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+  command
+    .name(NAME)
+    .description("Search Spotify for something to play")
+    .create_option(|option| {
+      option
+        .name("query")
+        .description("What to search for")
+        .kind(CommandOptionType::String)
+        .set_autocomplete(true)
+        .required(true)
+    })
+}
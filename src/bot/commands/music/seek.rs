@@ -0,0 +1,165 @@
+use serenity::{
+  builder::CreateApplicationCommand,
+  model::prelude::{
+    command::CommandOptionType, interaction::application_command::ApplicationCommandInteraction,
+  },
+  prelude::Context,
+};
+
+use crate::{
+  bot::commands::{respond_message, CommandOutput},
+  session::manager::SessionManager,
+  utils::{
+    self,
+    embed::{EmbedBuilder, Status},
+  },
+};
+
+pub const NAME: &str = "seek";
+
+pub fn command(ctx: Context, command: ApplicationCommandInteraction) -> CommandOutput {
+  Box::pin(async move {
+    let data = ctx.data.read().await;
+    let session_manager = data
+      .get::<SessionManager>()
+      .expect("to contain a value")
+      .clone();
+
+    let guild_id = command.guild_id.expect("to contain a value");
+
+    let session = match session_manager.get_session(guild_id).await {
+      Some(session) => session,
+      None => {
+        respond_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .title("Cannot seek")
+            .icon_url("https://spoticord.com/forbidden.png")
+            .description("I'm currently not playing any music in this server")
+            .status(Status::Error)
+            .build(),
+          true,
+        )
+        .await;
+
+        return;
+      }
+    };
+
+    if session.owner().await != Some(command.user.id) {
+      respond_message(
+        &ctx,
+        &command,
+        EmbedBuilder::new()
+          .title("Cannot seek")
+          .icon_url("https://spoticord.com/forbidden.png")
+          .description("You must be the host to seek")
+          .status(Status::Error)
+          .build(),
+        true,
+      )
+      .await;
+
+      return;
+    }
+
+    let pbi = match session.playback_info().await {
+      Some(pbi) => pbi,
+      None => {
+        respond_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .title("Cannot seek")
+            .icon_url("https://spoticord.com/forbidden.png")
+            .description("I'm currently not playing any music in this server")
+            .status(Status::Error)
+            .build(),
+          true,
+        )
+        .await;
+
+        return;
+      }
+    };
+
+    let position = match command
+      .data
+      .options
+      .get(0)
+      .and_then(|option| option.value.as_ref())
+      .and_then(|value| value.as_str())
+      .and_then(utils::str_to_time)
+    {
+      // Without a known duration there is nothing to check against
+      Some(position)
+        if pbi.duration_ms == 0 || position.saturating_mul(1000) <= pbi.duration_ms =>
+      {
+        position
+      }
+      Some(_) => {
+        respond_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .description(format!(
+              "**{}** is only {} long.",
+              pbi.get_name(),
+              utils::time_to_str(pbi.duration_ms / 1000)
+            ))
+            .status(Status::Error)
+            .build(),
+          true,
+        )
+        .await;
+
+        return;
+      }
+      None => {
+        respond_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .description("You need to provide a position like `1:30`.")
+            .status(Status::Error)
+            .build(),
+          true,
+        )
+        .await;
+
+        return;
+      }
+    };
+
+    session.seek(position.saturating_mul(1000)).await;
+
+    respond_message(
+      &ctx,
+      &command,
+      EmbedBuilder::new()
+        .description(format!(
+          "Skipped to **{}** in **{}**",
+          utils::time_to_str(position),
+          pbi.get_name()
+        ))
+        .status(Status::Success)
+        .build(),
+      false,
+    )
+    .await;
+  })
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+  command
+    .name(NAME)
+    .description("Skip to a position in the current track")
+    .create_option(|option| {
+      option
+        .name("position")
+        .description("The position to skip to, like 1:30")
+        .kind(CommandOptionType::String)
+        .required(true)
+    })
+}
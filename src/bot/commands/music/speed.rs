@@ -0,0 +1,133 @@
+use serenity::{
+  builder::CreateApplicationCommand,
+  model::prelude::{
+    command::CommandOptionType, interaction::application_command::ApplicationCommandInteraction,
+  },
+  prelude::Context,
+};
+
+use crate::{
+  bot::commands::{respond_message, CommandOutput},
+  session::{manager::SessionManager, pbi::CurrentTrack},
+  utils::embed::{EmbedBuilder, Status},
+};
+
+pub const NAME: &str = "speed";
+
+/// The slowest speed episodes can be played at
+const MIN_SPEED: f64 = 0.75;
+
+/// The fastest speed episodes can be played at
+const MAX_SPEED: f64 = 2.0;
+
+pub fn command(ctx: Context, command: ApplicationCommandInteraction) -> CommandOutput {
+  Box::pin(async move {
+    let data = ctx.data.read().await;
+    let session_manager = data
+      .get::<SessionManager>()
+      .expect("to contain a value")
+      .clone();
+
+    let guild_id = command.guild_id.expect("to contain a value");
+
+    let session = match session_manager.get_session(guild_id).await {
+      Some(session) => session,
+      None => {
+        respond_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .title("Cannot change playback speed")
+            .icon_url("https://spoticord.com/forbidden.png")
+            .description("I'm currently not playing any music in this server")
+            .status(Status::Error)
+            .build(),
+          true,
+        )
+        .await;
+
+        return;
+      }
+    };
+
+    if session.owner().await != Some(command.user.id) {
+      respond_message(
+        &ctx,
+        &command,
+        EmbedBuilder::new()
+          .title("Cannot change playback speed")
+          .icon_url("https://spoticord.com/forbidden.png")
+          .description("You must be the host to change the playback speed")
+          .status(Status::Error)
+          .build(),
+        true,
+      )
+      .await;
+
+      return;
+    }
+
+    let speed = match command
+      .data
+      .options
+      .get(0)
+      .and_then(|option| option.value.as_ref())
+      .and_then(|value| value.as_f64())
+      .filter(|speed| (MIN_SPEED..=MAX_SPEED).contains(speed))
+    {
+      Some(speed) => speed,
+      None => {
+        respond_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .description(format!(
+              "The playback speed must be between {MIN_SPEED}x and {MAX_SPEED}x."
+            ))
+            .status(Status::Error)
+            .build(),
+          true,
+        )
+        .await;
+
+        return;
+      }
+    };
+
+    session.set_speed(speed as f32).await;
+
+    // Music is always played at normal speed, so let the user know when it won't have any effect yet
+    let description = match session.playback_info().await.map(|pbi| pbi.track) {
+      Some(CurrentTrack::Episode(_)) => format!("Episodes are now played at **{speed}x**"),
+      _ => format!(
+        "Episodes are now played at **{speed}x**. This only applies to podcast episodes, music is always played at normal speed."
+      ),
+    };
+
+    respond_message(
+      &ctx,
+      &command,
+      EmbedBuilder::new()
+        .description(description)
+        .status(Status::Success)
+        .build(),
+      false,
+    )
+    .await;
+  })
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+  command
+    .name(NAME)
+    .description("Change the playback speed of podcast episodes")
+    .create_option(|option| {
+      option
+        .name("speed")
+        .description("The playback speed, from 0.75x up to 2x")
+        .kind(CommandOptionType::Number)
+        .min_number_value(MIN_SPEED)
+        .max_number_value(MAX_SPEED)
+        .required(true)
+    })
+}
@@ -0,0 +1,129 @@
+use log::error;
+use serenity::{
+  builder::CreateApplicationCommand,
+  model::prelude::{
+    command::CommandOptionType, interaction::application_command::ApplicationCommandInteraction,
+  },
+  prelude::Context,
+};
+
+use crate::{
+  bot::commands::{respond_message, CommandOutput},
+  database::Database,
+  session::manager::SessionManager,
+  utils::embed::{EmbedBuilder, Status},
+};
+
+pub const NAME: &str = "volume";
+
+pub fn command(ctx: Context, command: ApplicationCommandInteraction) -> CommandOutput {
+  Box::pin(async move {
+    let data = ctx.data.read().await;
+    let database = data.get::<Database>().expect("to contain a value");
+    let session_manager = data
+      .get::<SessionManager>()
+      .expect("to contain a value")
+      .clone();
+
+    let session = match session_manager
+      .get_session(command.guild_id.expect("to contain a value"))
+      .await
+    {
+      Some(session) => session,
+      None => {
+        respond_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .title("Cannot change volume")
+            .icon_url("https://spoticord.com/forbidden.png")
+            .description("I'm currently not playing any music in this server")
+            .status(Status::Error)
+            .build(),
+          true,
+        )
+        .await;
+
+        return;
+      }
+    };
+
+    if session.owner().await != Some(command.user.id) {
+      respond_message(
+        &ctx,
+        &command,
+        EmbedBuilder::new()
+          .title("Cannot change volume")
+          .icon_url("https://spoticord.com/forbidden.png")
+          .description("You must be the host to change the volume")
+          .status(Status::Error)
+          .build(),
+        true,
+      )
+      .await;
+
+      return;
+    }
+
+    let volume = match command
+      .data
+      .options
+      .get(0)
+      .and_then(|option| option.value.as_ref())
+      .and_then(|value| value.as_u64())
+      .filter(|volume| *volume <= 100)
+    {
+      Some(volume) => volume as u8,
+      None => {
+        respond_message(
+          &ctx,
+          &command,
+          EmbedBuilder::new()
+            .description("You need to provide a volume between 0 and 100.")
+            .status(Status::Error)
+            .build(),
+          true,
+        )
+        .await;
+
+        return;
+      }
+    };
+
+    session.set_volume(volume).await;
+
+    // Remember the volume, so that it is restored the next time this user hosts
+    if let Err(why) = database
+      .update_user_volume(command.user.id.to_string(), volume)
+      .await
+    {
+      error!("Failed to update user volume: {:?}", why);
+    }
+
+    respond_message(
+      &ctx,
+      &command,
+      EmbedBuilder::new()
+        .description(format!("The volume is now set to **{}%**", volume))
+        .status(Status::Success)
+        .build(),
+      false,
+    )
+    .await;
+  })
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+  command
+    .name(NAME)
+    .description("Change the volume of the bot in this server")
+    .create_option(|option| {
+      option
+        .name("level")
+        .description("The volume, in percent")
+        .kind(CommandOptionType::Integer)
+        .min_int_value(0)
+        .max_int_value(100)
+        .required(true)
+    })
+}
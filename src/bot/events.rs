@@ -7,7 +7,7 @@ use serenity::{
   async_trait,
   model::prelude::{
     interaction::{
-      application_command::ApplicationCommandInteraction,
+      application_command::ApplicationCommandInteraction, autocomplete::AutocompleteInteraction,
       message_component::MessageComponentInteraction, Interaction,
     },
     Activity, GuildId, Ready,
@@ -56,6 +56,7 @@ impl EventHandler for Handler {
     match interaction {
       Interaction::ApplicationCommand(command) => self.handle_command(ctx, command).await,
       Interaction::MessageComponent(component) => self.handle_component(ctx, component).await,
+      Interaction::Autocomplete(autocomplete) => self.handle_autocomplete(ctx, autocomplete).await,
       _ => {}
     }
   }
@@ -135,4 +136,28 @@ impl Handler {
 
     command_manager.execute_component(&ctx, component).await;
   }
+
+  async fn handle_autocomplete(&self, ctx: Context, autocomplete: AutocompleteInteraction) {
+    enforce_guild!(autocomplete);
+
+    // Autocompletion only exists for commands, which can only be executed inside of guilds
+    let guild_id = match autocomplete.guild_id {
+      Some(guild_id) => guild_id,
+      None => return,
+    };
+
+    trace!(
+      "Received autocomplete interaction: command={} user={} guild={}",
+      autocomplete.data.name,
+      autocomplete.user.id,
+      guild_id
+    );
+
+    let data = ctx.data.read().await;
+    let command_manager = data.get::<CommandManager>().expect("to contain a value");
+
+    command_manager
+      .execute_autocomplete(&ctx, autocomplete)
+      .await;
+  }
 }
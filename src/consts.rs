@@ -8,3 +8,18 @@ pub const MOTD: &str = "some good 'ol music";
 
 /// The time it takes for Spoticord to disconnect when no music is being played
 pub const DISCONNECT_TIME: u64 = 5 * 60;
+
+/// The volume (in percent) used for users that never changed their volume
+pub const DEFAULT_VOLUME: u8 = 50;
+
+/// How many times Spoticord tries to reconnect to Spotify after losing the connection
+pub const RECONNECT_ATTEMPTS: u32 = 5;
+
+/// The time (in seconds) before the first reconnect attempt, doubled after every failed attempt
+pub const RECONNECT_DELAY: u64 = 2;
+
+/// How long (in seconds) before it expires the access token of a host is renewed
+pub const TOKEN_REFRESH_MARGIN: u64 = 5 * 60;
+
+/// The minimum time (in seconds) between two attempts to renew an access token
+pub const TOKEN_REFRESH_INTERVAL: u64 = 60;
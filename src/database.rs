@@ -1,12 +1,17 @@
 use thiserror::Error;
 
+use librespot::discovery::Credentials;
 use log::trace;
 use reqwest::{header::HeaderMap, Client, Error, Response, StatusCode};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::{json, Value};
 use serenity::prelude::TypeMapKey;
 
-use crate::utils;
+use crate::{
+  audio::{channels::ChannelMix, eq::EqPreset},
+  player::bitrate::BitrateSetting,
+  utils,
+};
 
 #[derive(Debug, Error)]
 pub enum DatabaseError {
@@ -33,6 +38,12 @@ struct GetAccessTokenResponse {
 pub struct User {
   pub id: String,
   pub device_name: String,
+  #[serde(default)]
+  pub volume: Option<u8>,
+
+  /// Whether playback is moved to Spoticord when it joins, enabled when not set
+  #[serde(default)]
+  pub auto_transfer: Option<bool>,
   pub request: Option<Request>,
   pub accounts: Option<Vec<Account>>,
 }
@@ -53,6 +64,62 @@ pub struct Request {
   pub expires: u64,
 }
 
+#[derive(Default, Serialize, Deserialize)]
+pub struct GuildSettings {
+  #[serde(default)]
+  pub eq_preset: EqPreset,
+
+  #[serde(default)]
+  pub normalisation: NormalisationSettings,
+
+  /// Overrides the bitrate of the deployment when set
+  #[serde(default)]
+  pub bitrate: Option<BitrateSetting>,
+
+  #[serde(default)]
+  pub channels: ChannelMix,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NormalisationKind {
+  Track,
+  Album,
+  #[default]
+  Auto,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NormalisationSettings {
+  pub enabled: bool,
+  pub kind: NormalisationKind,
+  pub pregain_db: f64,
+  pub threshold_dbfs: f64,
+
+  /// Whether to use the dynamic limiter instead of a basic gain reduction
+  pub limiter: bool,
+  pub limiter_attack_ms: u64,
+  pub limiter_release_ms: u64,
+  pub limiter_knee_db: f64,
+}
+
+impl Default for NormalisationSettings {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      kind: NormalisationKind::Auto,
+      pregain_db: 0.0,
+      threshold_dbfs: -2.0,
+      limiter: true,
+      limiter_attack_ms: 5,
+      limiter_release_ms: 100,
+      limiter_knee_db: 5.0,
+    }
+  }
+}
+
+#[derive(Clone)]
 pub struct Database {
   base_url: String,
   default_headers: Option<HeaderMap>,
@@ -288,6 +355,73 @@ impl Database {
     Ok(body)
   }
 
+  // Get the reusable librespot credentials of a user, kept next to their Spotify account
+  pub async fn get_user_credentials(
+    &self,
+    user_id: impl Into<String> + Send,
+  ) -> Result<Credentials, DatabaseError> {
+    self
+      .simple_get(format!("/account/{}/spotify/credentials", user_id.into()))
+      .await
+  }
+
+  // Replace the reusable librespot credentials of a user
+  pub async fn update_user_credentials(
+    &self,
+    user_id: impl Into<String> + Send,
+    credentials: &Credentials,
+  ) -> Result<(), DatabaseError> {
+    let body = json!(credentials);
+
+    let response = match self
+      .request(RequestOptions {
+        method: Method::Put,
+        path: format!("/account/{}/spotify/credentials", user_id.into()),
+        body: Some(Body::Json(body)),
+        headers: None,
+      })
+      .await
+    {
+      Ok(response) => response,
+      Err(err) => return Err(DatabaseError::IOError(err.to_string())),
+    };
+
+    match response.status() {
+      StatusCode::OK | StatusCode::CREATED | StatusCode::ACCEPTED | StatusCode::NO_CONTENT => {
+        Ok(())
+      }
+      status => Err(DatabaseError::InvalidStatusCode(status)),
+    }
+  }
+
+  // Remove the reusable librespot credentials of a user, e.g. when their account is unlinked
+  pub async fn delete_user_credentials(
+    &self,
+    user_id: impl Into<String> + Send,
+  ) -> Result<(), DatabaseError> {
+    let response = match self
+      .request(RequestOptions {
+        method: Method::Delete,
+        path: format!("/account/{}/spotify/credentials", user_id.into()),
+        body: None,
+        headers: None,
+      })
+      .await
+    {
+      Ok(response) => response,
+      Err(err) => return Err(DatabaseError::IOError(err.to_string())),
+    };
+
+    match response.status() {
+      StatusCode::OK | StatusCode::CREATED | StatusCode::ACCEPTED | StatusCode::NO_CONTENT => {}
+      // Nothing was stored, which is just as good
+      StatusCode::NOT_FOUND => {}
+      status => return Err(DatabaseError::InvalidStatusCode(status)),
+    };
+
+    Ok(())
+  }
+
   pub async fn delete_user_account(
     &self,
     user_id: impl Into<String> + Send,
@@ -348,6 +482,122 @@ impl Database {
       status => Err(DatabaseError::InvalidStatusCode(status)),
     }
   }
+
+  pub async fn update_user_volume(
+    &self,
+    user_id: impl Into<String>,
+    volume: u8,
+  ) -> Result<(), DatabaseError> {
+    if volume > 100 {
+      return Err(DatabaseError::InvalidInputBody("Invalid volume".into()));
+    }
+
+    let body = json!({ "volume": volume });
+
+    let response = match self
+      .request(RequestOptions {
+        method: Method::Patch,
+        path: format!("/user/{}", user_id.into()),
+        body: Some(Body::Json(body)),
+        headers: None,
+      })
+      .await
+    {
+      Ok(response) => response,
+      Err(err) => return Err(DatabaseError::IOError(err.to_string())),
+    };
+
+    match response.status() {
+      StatusCode::OK | StatusCode::CREATED | StatusCode::ACCEPTED | StatusCode::NO_CONTENT => {
+        Ok(())
+      }
+      status => Err(DatabaseError::InvalidStatusCode(status)),
+    }
+  }
+
+  pub async fn update_user_auto_transfer(
+    &self,
+    user_id: impl Into<String>,
+    enabled: bool,
+  ) -> Result<(), DatabaseError> {
+    let body = json!({ "auto_transfer": enabled });
+
+    let response = match self
+      .request(RequestOptions {
+        method: Method::Patch,
+        path: format!("/user/{}", user_id.into()),
+        body: Some(Body::Json(body)),
+        headers: None,
+      })
+      .await
+    {
+      Ok(response) => response,
+      Err(err) => return Err(DatabaseError::IOError(err.to_string())),
+    };
+
+    match response.status() {
+      StatusCode::OK | StatusCode::CREATED | StatusCode::ACCEPTED | StatusCode::NO_CONTENT => {
+        Ok(())
+      }
+      status => Err(DatabaseError::InvalidStatusCode(status)),
+    }
+  }
+
+  // Get the settings of a guild, guilds that never changed their settings get the defaults
+  pub async fn get_guild_settings(
+    &self,
+    guild_id: impl Into<String>,
+  ) -> Result<GuildSettings, DatabaseError> {
+    let path = format!("/guild/{}/settings", guild_id.into());
+
+    match self.simple_get(path).await {
+      Err(DatabaseError::InvalidStatusCode(StatusCode::NOT_FOUND)) => Ok(GuildSettings::default()),
+      result => result,
+    }
+  }
+
+  // Change one or more settings of a guild
+  pub async fn modify_guild_settings(
+    &self,
+    guild_id: impl Into<String>,
+    modify: impl FnOnce(&mut GuildSettings),
+  ) -> Result<(), DatabaseError> {
+    let guild_id: String = guild_id.into();
+    let mut settings = self.get_guild_settings(&guild_id).await?;
+
+    modify(&mut settings);
+
+    self.update_guild_settings(guild_id, &settings).await
+  }
+
+  // Update the settings of a guild
+  pub async fn update_guild_settings(
+    &self,
+    guild_id: impl Into<String>,
+    settings: &GuildSettings,
+  ) -> Result<(), DatabaseError> {
+    let body = json!(settings);
+
+    let response = match self
+      .request(RequestOptions {
+        method: Method::Put,
+        path: format!("/guild/{}/settings", guild_id.into()),
+        body: Some(Body::Json(body)),
+        headers: None,
+      })
+      .await
+    {
+      Ok(response) => response,
+      Err(err) => return Err(DatabaseError::IOError(err.to_string())),
+    };
+
+    match response.status() {
+      StatusCode::OK | StatusCode::CREATED | StatusCode::ACCEPTED | StatusCode::NO_CONTENT => {
+        Ok(())
+      }
+      status => Err(DatabaseError::InvalidStatusCode(status)),
+    }
+  }
 }
 
 impl TypeMapKey for Database {
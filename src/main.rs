@@ -114,6 +114,15 @@ async fn main() {
             if let Err(why) = stats_manager.set_active_count(active_count) {
               error!("Failed to update active count: {why}");
             }
+
+            for session in session_manager.sessions().await {
+              let guild_id = session.guild_id().await;
+              let telemetry = session.audio_telemetry().await;
+
+              if let Err(why) = stats_manager.set_audio_telemetry(guild_id, &telemetry) {
+                error!("Failed to update audio telemetry: {why}");
+              }
+            }
           }
         }
 
@@ -0,0 +1,82 @@
+use std::str::FromStr;
+
+use librespot::playback::config::Bitrate;
+use serde::{Deserialize, Serialize};
+
+/// The bitrate at which audio is fetched from Spotify
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BitrateSetting {
+  /// Pick the bitrate based on the bitrate of the voice channel
+  #[serde(rename = "auto")]
+  Auto,
+
+  #[default]
+  #[serde(rename = "96")]
+  Kbps96,
+
+  #[serde(rename = "160")]
+  Kbps160,
+
+  #[serde(rename = "320")]
+  Kbps320,
+}
+
+impl BitrateSetting {
+  pub const ALL: [BitrateSetting; 4] = [Self::Auto, Self::Kbps96, Self::Kbps160, Self::Kbps320];
+
+  /// The identifier used in commands, environment variables and the database
+  pub fn id(&self) -> &'static str {
+    match self {
+      Self::Auto => "auto",
+      Self::Kbps96 => "96",
+      Self::Kbps160 => "160",
+      Self::Kbps320 => "320",
+    }
+  }
+
+  /// The human readable name of the setting
+  pub fn name(&self) -> &'static str {
+    match self {
+      Self::Auto => "Automatic",
+      Self::Kbps96 => "96 kbps",
+      Self::Kbps160 => "160 kbps",
+      Self::Kbps320 => "320 kbps",
+    }
+  }
+
+  /// Read the bitrate from the `SPOTIFY_BITRATE` environment variable, defaulting to 96 kbps
+  pub fn from_env() -> Self {
+    match std::env::var("SPOTIFY_BITRATE") {
+      Ok(value) => value.to_lowercase().parse().unwrap_or_else(|_| {
+        log::warn!("Unknown Spotify bitrate '{value}', falling back to '96'");
+        Self::Kbps96
+      }),
+      Err(_) => Self::Kbps96,
+    }
+  }
+
+  /// Get the bitrate librespot should use, given the bitrate (in bps) of the voice channel
+  pub fn resolve(&self, channel_bitrate: Option<u64>) -> Bitrate {
+    match self {
+      Self::Auto => match channel_bitrate.unwrap_or_default() {
+        bitrate if bitrate >= 256_000 => Bitrate::Bitrate320,
+        bitrate if bitrate >= 128_000 => Bitrate::Bitrate160,
+        _ => Bitrate::Bitrate96,
+      },
+      Self::Kbps96 => Bitrate::Bitrate96,
+      Self::Kbps160 => Bitrate::Bitrate160,
+      Self::Kbps320 => Bitrate::Bitrate320,
+    }
+  }
+}
+
+impl FromStr for BitrateSetting {
+  type Err = ();
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Self::ALL
+      .into_iter()
+      .find(|setting| setting.id() == s)
+      .ok_or(())
+  }
+}
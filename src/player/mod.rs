@@ -1,4 +1,13 @@
-use std::{io::Write, sync::Arc};
+pub mod bitrate;
+
+use std::{
+  io::Write,
+  sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc,
+  },
+  time::Duration,
+};
 
 use anyhow::{anyhow, Result};
 use librespot::{
@@ -10,28 +19,39 @@ use librespot::{
   },
   discovery::Credentials,
   playback::{
-    config::{Bitrate, PlayerConfig, VolumeCtrl},
-    mixer::{self, MixerConfig},
-    player::{Player as SpotifyPlayer, PlayerEvent as SpotifyEvent},
+    config::{Bitrate, NormalisationMethod, NormalisationType, PlayerConfig, VolumeCtrl},
+    mixer::{softmixer::SoftMixer, Mixer, MixerConfig},
+    player::{duration_to_coefficient, Player as SpotifyPlayer, PlayerEvent as SpotifyEvent},
   },
   protocol::metadata::{Episode, Track},
 };
-use log::error;
+use log::{error, warn};
 use protobuf::Message;
 use songbird::tracks::TrackHandle;
-use tokio::sync::{
-  broadcast::{Receiver, Sender},
-  mpsc::UnboundedReceiver,
-  Mutex,
+use tokio::{
+  sync::{
+    broadcast::{Receiver, Sender},
+    mpsc::UnboundedReceiver,
+    Mutex,
+  },
+  task::JoinHandle,
 };
 
 use crate::{
-  audio::{stream::Stream, SinkEvent, StreamSink},
+  audio::{settings::AudioSettings, stream::Stream, SinkEvent, StreamSink},
+  consts::DEFAULT_VOLUME,
+  database::{GuildSettings, NormalisationKind, User},
   librespot_ext::discovery::CredentialsExt,
   session::pbi::{CurrentTrack, PlaybackInfo},
-  utils,
+  utils::{self, spotify::RepeatMode},
 };
 
+/// How far (in milliseconds) the reported position may drift before it counts as a seek
+const SEEK_TOLERANCE_MS: u32 = 2000;
+
+/// How long to wait for player events to settle before asking Spotify for shuffle and repeat
+const PLAY_MODE_REFRESH_DELAY: Duration = Duration::from_secs(1);
+
 enum Event {
   Player(SpotifyEvent),
   Sink(SinkEvent),
@@ -44,51 +64,171 @@ enum PlayerCommand {
   Previous,
   Pause,
   Play,
+  SetVolume(u16),
+  Seek(u32),
+  SetShuffle(bool),
+  SetRepeat(RepeatMode),
+  SetToken(String),
   Shutdown,
 }
 
 #[derive(Clone, Debug)]
 pub enum PlayerEvent {
-  Pause,
-  Play,
+  Pause(Option<PlaybackInfo>),
+  Play(Option<PlaybackInfo>),
+
+  /// Another track or episode is being played
+  TrackChanged(PlaybackInfo),
+
+  /// The track or episode was played until the end
+  EndOfTrack(Option<PlaybackInfo>),
+
+  /// A track or episode could not be played, Spotify will skip to the next one
+  Unavailable(SpotifyId, Option<PlaybackInfo>),
+
+  /// The volume (in percent) was changed, either by us or by Spotify
+  VolumeChanged(u8, Option<PlaybackInfo>),
+
+  /// The position in the current track or episode jumped
+  Seeked(PlaybackInfo),
+
+  /// The connection to Spotify went away, followed by `Stopped`
+  SessionLost(Option<PlaybackInfo>),
+
+  /// The player ran into an error and is shutting down, followed by `Stopped`
+  Failed(String),
   Stopped,
 }
 
+/// Keeps shuffle and repeat refreshes from the Web API from undoing changes made through Spoticord
+#[derive(Default)]
+struct PlayModeSync {
+  /// Increased on every change made through Spoticord
+  generation: AtomicU64,
+
+  /// The amount of changes that have not been sent to Spotify yet
+  pending: AtomicUsize,
+}
+
+impl PlayModeSync {
+  fn begin_change(&self) {
+    self.generation.fetch_add(1, Ordering::SeqCst);
+    self.pending.fetch_add(1, Ordering::SeqCst);
+  }
+
+  fn end_change(&self) {
+    self.pending.fetch_sub(1, Ordering::SeqCst);
+  }
+
+  /// The current generation, or `None` while changes are still being sent to Spotify
+  fn generation(&self) -> Option<u64> {
+    match self.pending.load(Ordering::SeqCst) {
+      0 => Some(self.generation.load(Ordering::SeqCst)),
+      _ => None,
+    }
+  }
+}
+
 #[derive(Clone)]
 pub struct Player {
   tx: Sender<PlayerCommand>,
+  mixer: SoftMixer,
 
+  /// The ID of the Spotify Connect device, as used by the Web API
+  device_id: String,
+
+  session: Session,
+  credentials: Credentials,
+  play_mode: Arc<PlayModeSync>,
   pbi: Arc<Mutex<Option<PlaybackInfo>>>,
 }
 
+/// A connection to Spotify on behalf of the owner, which a player is started on
+pub struct Connection {
+  session: Session,
+
+  /// The reusable credentials Spotify handed out for this connection
+  credentials: Credentials,
+
+  /// The Web API token of the owner, only known up front when it was used to log in
+  token: Option<String>,
+}
+
+impl Connection {
+  /// Log in with reusable credentials from an earlier connection
+  pub async fn with_credentials(credentials: Credentials) -> Result<Self> {
+    let (session, credentials) =
+      Session::connect(session_config(), credentials, None, false).await?;
+
+    Ok(Self {
+      session,
+      credentials,
+      token: None,
+    })
+  }
+
+  /// Log in with the access token of the owner
+  pub async fn with_token(token: String) -> Result<Self> {
+    let username = utils::spotify::get_username(&token).await?;
+    let credentials = Credentials::with_token(username, &token);
+    let (session, credentials) =
+      Session::connect(session_config(), credentials, None, false).await?;
+
+    Ok(Self {
+      session,
+      credentials,
+      token: Some(token),
+    })
+  }
+
+  pub fn has_token(&self) -> bool {
+    self.token.is_some()
+  }
+}
+
 impl Player {
   pub async fn create(
     stream: Stream,
-    token: &str,
-    device_name: &str,
+    connection: Connection,
+    user: &User,
     track: TrackHandle,
+    settings: AudioSettings,
+    guild_settings: &GuildSettings,
+    bitrate: Bitrate,
   ) -> Result<(Self, Receiver<PlayerEvent>)> {
-    let username = utils::spotify::get_username(token).await?;
-
+    let normalisation = &guild_settings.normalisation;
     let player_config = PlayerConfig {
-      bitrate: Bitrate::Bitrate96,
+      bitrate,
+      normalisation: normalisation.enabled,
+      normalisation_type: match normalisation.kind {
+        NormalisationKind::Track => NormalisationType::Track,
+        NormalisationKind::Album => NormalisationType::Album,
+        NormalisationKind::Auto => NormalisationType::Auto,
+      },
+      normalisation_method: if normalisation.limiter {
+        NormalisationMethod::Dynamic
+      } else {
+        NormalisationMethod::Basic
+      },
+      normalisation_pregain_db: normalisation.pregain_db,
+      normalisation_threshold_dbfs: normalisation.threshold_dbfs,
+      normalisation_attack_cf: duration_to_coefficient(Duration::from_millis(
+        normalisation.limiter_attack_ms,
+      )),
+      normalisation_release_cf: duration_to_coefficient(Duration::from_millis(
+        normalisation.limiter_release_ms,
+      )),
+      normalisation_knee_db: normalisation.limiter_knee_db,
       ..Default::default()
     };
 
-    let credentials = Credentials::with_token(username, token);
-
-    let (session, _) = Session::connect(
-      SessionConfig {
-        ap_port: Some(9999), // Force the use of ap.spotify.com, which has the lowest latency
-        ..Default::default()
-      },
+    let Connection {
+      session,
       credentials,
-      None,
-      false,
-    )
-    .await?;
+      token,
+    } = connection;
 
-    let mixer = (mixer::find(Some("softvol")).expect("to exist"))(MixerConfig {
+    let mixer = SoftMixer::open(MixerConfig {
       volume_ctrl: VolumeCtrl::Linear,
       ..Default::default()
     });
@@ -97,43 +237,62 @@ impl Player {
     let (player, rx_player) =
       SpotifyPlayer::new(player_config, session.clone(), mixer.get_soft_volume(), {
         let stream = stream.clone();
-        move || Box::new(StreamSink::new(stream, tx))
+        let settings = settings.clone();
+        move || Box::new(StreamSink::new(stream, tx, settings))
       });
 
     let (spirc, spirc_task) = Spirc::new(
       ConnectConfig {
-        name: device_name.into(),
-        // 50%
-        initial_volume: Some(65535 / 2),
+        name: user.device_name.clone(),
+        initial_volume: Some(percent_to_volume(user.volume.unwrap_or(DEFAULT_VOLUME))),
         // Default Spotify behaviour
         autoplay: true,
         ..Default::default()
       },
       session.clone(),
       player,
-      mixer,
+      Box::new(mixer.clone()),
     );
 
     let (tx, rx) = tokio::sync::broadcast::channel(10);
-    let (tx_ev, rx_ev) = tokio::sync::broadcast::channel(10);
+    let (tx_ev, rx_ev) = tokio::sync::broadcast::channel(32);
     let pbi = Arc::new(Mutex::new(None));
+    let device_id = session.device_id().to_string();
+    let play_mode = Arc::new(PlayModeSync::default());
 
     let player_task = PlayerTask {
       pbi: pbi.clone(),
+      play_mode: play_mode.clone(),
+      play_mode_refresh: None,
+      session_lost: false,
+      token,
       session: session.clone(),
       rx_player,
       rx_sink,
       rx,
       tx: tx_ev,
       spirc,
+      mixer: mixer.clone(),
       track,
       stream,
+      settings,
     };
 
     tokio::spawn(spirc_task);
     tokio::spawn(player_task.run());
 
-    Ok((Self { pbi, tx }, rx_ev))
+    Ok((
+      Self {
+        pbi,
+        tx,
+        mixer,
+        device_id,
+        session,
+        credentials,
+        play_mode,
+      },
+      rx_ev,
+    ))
   }
 
   pub fn next(&self) {
@@ -152,6 +311,108 @@ impl Player {
     self.tx.send(PlayerCommand::Play).ok();
   }
 
+  /// Change the volume, in percent
+  pub fn set_volume(&self, volume: u8) {
+    self
+      .tx
+      .send(PlayerCommand::SetVolume(percent_to_volume(volume)))
+      .ok();
+  }
+
+  /// Get the current volume, in percent
+  pub fn volume(&self) -> u8 {
+    volume_to_percent(self.mixer.volume())
+  }
+
+  /// Seek to a position (in milliseconds) in the current track
+  pub async fn seek(&self, position_ms: u32) {
+    // Show the new position right away, instead of once Spotify has confirmed it
+    if let Some(pbi) = self.pbi.lock().await.as_mut() {
+      pbi.seek(position_ms);
+    }
+
+    self.tx.send(PlayerCommand::Seek(position_ms)).ok();
+  }
+
+  /// Turn shuffling on or off
+  pub async fn set_shuffle(&self, shuffle: bool) {
+    self.play_mode.begin_change();
+
+    if let Some(pbi) = self.pbi.lock().await.as_mut() {
+      pbi.shuffle = shuffle;
+    }
+
+    self.tx.send(PlayerCommand::SetShuffle(shuffle)).ok();
+  }
+
+  /// Change the repeat mode
+  pub async fn set_repeat(&self, repeat: RepeatMode) {
+    self.play_mode.begin_change();
+
+    if let Some(pbi) = self.pbi.lock().await.as_mut() {
+      pbi.repeat = repeat;
+    }
+
+    self.tx.send(PlayerCommand::SetRepeat(repeat)).ok();
+  }
+
+  /// Let the playback info know that episodes are now played at another speed
+  ///
+  /// The audio itself is sped up by the sink, which reads the speed from the audio settings.
+  pub async fn set_speed(&self, speed: f32) {
+    if let Some(pbi) = self.pbi.lock().await.as_mut() {
+      pbi.set_speed(speed);
+    }
+  }
+
+  pub fn device_id(&self) -> &str {
+    &self.device_id
+  }
+
+  /// Replace the access token used for Web API calls on behalf of the owner
+  pub fn set_token(&self, token: String) {
+    self.tx.send(PlayerCommand::SetToken(token)).ok();
+  }
+
+  /// Retrieve the metadata of multiple tracks or episodes, in the same order
+  ///
+  /// The ones that can't be resolved are kept as `CurrentTrack::Unknown`, so that the positions of
+  /// the others don't shift.
+  pub async fn resolve(&self, spotify_ids: &[SpotifyId]) -> Vec<CurrentTrack> {
+    let mut requests = Vec::with_capacity(spotify_ids.len());
+    let mut tracks = Vec::with_capacity(spotify_ids.len());
+
+    // Request everything at once, the results are collected in the original order
+    for &spotify_id in spotify_ids {
+      let session = self.session.clone();
+
+      requests.push(tokio::spawn(async move {
+        resolve_audio_info(&session, spotify_id).await
+      }));
+    }
+
+    for request in requests {
+      match request.await {
+        Ok(Ok(track)) => tracks.push(track),
+        Ok(Err(why)) => {
+          warn!("Failed to resolve queued track: {:?}", why);
+          tracks.push(CurrentTrack::Unknown);
+        }
+        Err(why) => {
+          error!("Metadata request task failed: {:?}", why);
+          tracks.push(CurrentTrack::Unknown);
+        }
+      }
+    }
+
+    tracks
+  }
+
+  /// The reusable credentials Spotify handed out for this connection
+  pub fn credentials(&self) -> &Credentials {
+    &self.credentials
+  }
+
   pub fn shutdown(&self) {
     self.tx.send(PlayerCommand::Shutdown).ok();
   }
@@ -162,9 +423,14 @@ impl Player {
 }
 
 struct PlayerTask {
+  /// The Web API token of the owner, which may only arrive after the player started
+  token: Option<String>,
+
   stream: Stream,
+  settings: AudioSettings,
   session: Session,
   spirc: Spirc,
+  mixer: SoftMixer,
   track: TrackHandle,
 
   rx_player: UnboundedReceiver<SpotifyEvent>,
@@ -173,6 +439,13 @@ struct PlayerTask {
   tx: Sender<PlayerEvent>,
 
   pbi: Arc<Mutex<Option<PlaybackInfo>>>,
+  play_mode: Arc<PlayModeSync>,
+
+  /// The scheduled shuffle and repeat refresh, replaced by every new player event
+  play_mode_refresh: Option<JoinHandle<()>>,
+
+  /// Set when the connection to Spotify went away, the next player takes over the stream
+  session_lost: bool,
 }
 
 impl PlayerTask {
@@ -194,10 +467,21 @@ impl PlayerTask {
             duration_ms,
           } => {
             self
+              .settings
+              .set_episode(track_id.audio_type == SpotifyAudioType::Podcast);
+
+            let seeked = self
               .update_pbi(track_id, position_ms, duration_ms, true)
               .await;
+            self.refresh_play_mode();
+
+            let pbi = self.snapshot().await;
 
-            self.tx.send(PlayerEvent::Play).ok();
+            if let (true, Some(pbi)) = (seeked, &pbi) {
+              self.tx.send(PlayerEvent::Seeked(pbi.clone())).ok();
+            }
+
+            self.tx.send(PlayerEvent::Play(pbi)).ok();
           }
 
           SpotifyEvent::Paused {
@@ -206,23 +490,37 @@ impl PlayerTask {
             position_ms,
             duration_ms,
           } => {
-            self
+            let seeked = self
               .update_pbi(track_id, position_ms, duration_ms, false)
               .await;
+            self.refresh_play_mode();
 
-            self.tx.send(PlayerEvent::Pause).ok();
+            let pbi = self.snapshot().await;
+
+            if let (true, Some(pbi)) = (seeked, &pbi) {
+              self.tx.send(PlayerEvent::Seeked(pbi.clone())).ok();
+            }
+
+            self.tx.send(PlayerEvent::Pause(pbi)).ok();
           }
 
           SpotifyEvent::Changed {
             old_track_id: _,
             new_track_id,
           } => {
-            if let Ok(current) = self.resolve_audio_info(new_track_id).await {
-              let mut pbi = self.pbi.lock().await;
+            // The sink crossfades into the new track, if crossfading is enabled
+            self.settings.signal_track_change();
+            self
+              .settings
+              .set_episode(new_track_id.audio_type == SpotifyAudioType::Podcast);
+
+            let current = self.resolve_or_unknown(new_track_id).await;
+            let mut pbi = self.pbi.lock().await;
+
+            if let Some(pbi) = pbi.as_mut() {
+              pbi.update_track(new_track_id, current);
 
-              if let Some(pbi) = pbi.as_mut() {
-                pbi.update_track(new_track_id, current);
-              }
+              self.tx.send(PlayerEvent::TrackChanged(pbi.clone())).ok();
             }
           }
 
@@ -230,9 +528,46 @@ impl PlayerTask {
             play_request_id: _,
             track_id: _,
           } => {
+            // Drop the audio that would otherwise play once the track is resumed
+            self.stream.flush().ok();
             check_result(self.track.pause());
 
-            self.tx.send(PlayerEvent::Pause).ok();
+            self.tx.send(PlayerEvent::Pause(self.snapshot().await)).ok();
+          }
+
+          SpotifyEvent::EndOfTrack {
+            play_request_id: _,
+            track_id: _,
+          } => {
+            self
+              .tx
+              .send(PlayerEvent::EndOfTrack(self.snapshot().await))
+              .ok();
+          }
+
+          SpotifyEvent::Unavailable {
+            play_request_id: _,
+            track_id,
+          } => {
+            warn!(
+              "Track {} is unavailable",
+              track_id.to_base62().unwrap_or_default()
+            );
+
+            self
+              .tx
+              .send(PlayerEvent::Unavailable(track_id, self.snapshot().await))
+              .ok();
+          }
+
+          SpotifyEvent::VolumeSet { volume } => {
+            self
+              .tx
+              .send(PlayerEvent::VolumeChanged(
+                volume_to_percent(volume),
+                self.snapshot().await,
+              ))
+              .ok();
           }
 
           _ => {}
@@ -252,7 +587,12 @@ impl PlayerTask {
 
             // check_result(track.pause());
 
-            self.tx.send(PlayerEvent::Pause).ok();
+            self.tx.send(PlayerEvent::Pause(self.snapshot().await)).ok();
+          }
+
+          SinkEvent::Failed(reason) => {
+            self.tx.send(PlayerEvent::Failed(reason)).ok();
+            break;
           }
         },
 
@@ -262,12 +602,30 @@ impl PlayerTask {
           PlayerCommand::Previous => self.spirc.prev(),
           PlayerCommand::Pause => self.spirc.pause(),
           PlayerCommand::Play => self.spirc.play(),
+          PlayerCommand::SetVolume(volume) => self.mixer.set_volume(volume),
+          PlayerCommand::Seek(position_ms) => {
+            self.seek(position_ms);
+
+            // The position is already updated, Spotify won't report it as a jump
+            if let Some(pbi) = self.snapshot().await {
+              self.tx.send(PlayerEvent::Seeked(pbi)).ok();
+            }
+          }
+          PlayerCommand::SetShuffle(shuffle) => self.set_shuffle(shuffle),
+          PlayerCommand::SetRepeat(repeat) => self.set_repeat(repeat),
+          PlayerCommand::SetToken(token) => self.token = Some(token),
           PlayerCommand::Shutdown => break,
         },
 
         None => {
-          // One of the channels died
-          log::debug!("Channel died");
+          // librespot's player or the sink went away without being told to
+          error!("Player channel died unexpectedly");
+
+          self.session_lost = true;
+          self
+            .tx
+            .send(PlayerEvent::SessionLost(self.snapshot().await))
+            .ok();
           break;
         }
       }
@@ -286,121 +644,282 @@ impl PlayerTask {
         event.map(Event::Sink)
       }
 
+      // Without a working command channel nobody can control the player, so shut it down
       command = self.rx.recv() => {
-        command.ok().map(Event::Command)
+        Some(Event::Command(command.unwrap_or(PlayerCommand::Shutdown)))
+      }
+    }
+  }
+
+  /// Seek through the Web API, as Spirc only seeks when Spotify tells it to
+  fn seek(&self, position_ms: u32) {
+    let Some(token) = self.token.clone() else {
+      warn!("Cannot seek without an access token");
+      return;
+    };
+
+    let device_id = self.session.device_id().to_string();
+
+    // Don't hold up the player events while waiting for Spotify
+    tokio::spawn(async move {
+      if let Err(why) = utils::spotify::seek(token, &device_id, position_ms).await {
+        error!("Failed to seek: {:?}", why);
       }
+    });
+  }
+
+  /// Change shuffling through the Web API, as Spirc can only toggle it without telling its state
+  fn set_shuffle(&self, shuffle: bool) {
+    let Some(token) = self.token.clone() else {
+      warn!("Cannot change shuffle without an access token");
+      self.play_mode.end_change();
+      return;
+    };
+
+    let device_id = self.session.device_id().to_string();
+    let play_mode = self.play_mode.clone();
+
+    tokio::spawn(async move {
+      if let Err(why) = utils::spotify::set_shuffle(token, &device_id, shuffle).await {
+        error!("Failed to change shuffle: {:?}", why);
+      }
+
+      play_mode.end_change();
+    });
+  }
+
+  /// Change the repeat mode through the Web API, as Spirc has no way to do so
+  fn set_repeat(&self, repeat: RepeatMode) {
+    let Some(token) = self.token.clone() else {
+      warn!("Cannot change the repeat mode without an access token");
+      self.play_mode.end_change();
+      return;
+    };
+
+    let device_id = self.session.device_id().to_string();
+    let play_mode = self.play_mode.clone();
+
+    tokio::spawn(async move {
+      if let Err(why) = utils::spotify::set_repeat(token, &device_id, repeat).await {
+        error!("Failed to change repeat mode: {:?}", why);
+      }
+
+      play_mode.end_change();
+    });
+  }
+
+  /// Pick up shuffle and repeat changes made elsewhere (e.g. in the Spotify app)
+  ///
+  /// librespot does not report these, so they are retrieved from the Web API instead. Events
+  /// often come in bursts, so only the last one of a burst leads to a request.
+  fn refresh_play_mode(&mut self) {
+    if let Some(handle) = self.play_mode_refresh.take() {
+      handle.abort();
     }
+
+    let Some(token) = self.token.clone() else {
+      return;
+    };
+
+    let pbi = self.pbi.clone();
+    let play_mode = self.play_mode.clone();
+
+    self.play_mode_refresh = Some(tokio::spawn(async move {
+      tokio::time::sleep(PLAY_MODE_REFRESH_DELAY).await;
+
+      // Spotify would still report the old state
+      let Some(generation) = play_mode.generation() else {
+        return;
+      };
+
+      match utils::spotify::get_play_mode(token).await {
+        Ok((shuffle, repeat)) => {
+          let mut pbi = pbi.lock().await;
+
+          // Don't undo a change that was made while waiting for Spotify
+          if play_mode.generation() != Some(generation) {
+            return;
+          }
+
+          if let Some(pbi) = pbi.as_mut() {
+            pbi.shuffle = shuffle;
+            pbi.repeat = repeat;
+          }
+        }
+        Err(why) => warn!("Failed to get play mode: {:?}", why),
+      }
+    }));
   }
 
   /// Update current playback info, or return early if not necessary
+  ///
+  /// Returns whether the position jumped away from where playback was expected to be.
   async fn update_pbi(
     &self,
     spotify_id: SpotifyId,
     position_ms: u32,
     duration_ms: u32,
     playing: bool,
-  ) {
+  ) -> bool {
     let mut pbi = self.pbi.lock().await;
 
+    let seeked = pbi
+      .as_ref()
+      .filter(|pbi| pbi.spotify_id == spotify_id)
+      .map(|pbi| pbi.get_position().abs_diff(position_ms) > SEEK_TOLERANCE_MS)
+      .unwrap_or(false);
+
     if let Some(pbi) = pbi.as_mut() {
       pbi.update_pos_dur(position_ms, duration_ms, playing);
     }
 
-    if !pbi
+    // Metadata is only resolved again when it failed before, NonPlayable items have none to resolve
+    if pbi
       .as_ref()
-      .map(|pbi| pbi.spotify_id == spotify_id)
-      .unwrap_or(true)
+      .map(|pbi| {
+        pbi.spotify_id != spotify_id
+          || !matches!(pbi.track, CurrentTrack::Unknown)
+          || spotify_id.audio_type == SpotifyAudioType::NonPlayable
+      })
+      .unwrap_or(false)
     {
-      return;
+      return seeked;
     }
 
-    if let Ok(current) = self.resolve_audio_info(spotify_id).await {
-      match pbi.as_mut() {
-        Some(pbi) => {
-          pbi.update_track(spotify_id, current);
-          pbi.update_pos_dur(position_ms, duration_ms, true);
-        }
-        None => {
-          *pbi = Some(PlaybackInfo::new(
-            duration_ms,
-            position_ms,
-            true,
-            current,
-            spotify_id,
-          ));
-        }
+    let current = self.resolve_or_unknown(spotify_id).await;
+
+    match pbi.as_mut() {
+      Some(pbi) => {
+        pbi.update_track(spotify_id, current);
+        pbi.update_pos_dur(position_ms, duration_ms, true);
+      }
+      None => {
+        *pbi = Some(PlaybackInfo::new(
+          duration_ms,
+          position_ms,
+          true,
+          current,
+          spotify_id,
+          self.settings.speed(),
+        ));
       }
-    } else {
-      log::error!("Failed to resolve audio info");
     }
+
+    seeked
   }
 
-  /// Retrieve the metadata for a `SpotifyId`
-  async fn resolve_audio_info(&self, spotify_id: SpotifyId) -> Result<CurrentTrack> {
-    match spotify_id.audio_type {
-      SpotifyAudioType::Track => self.resolve_track_info(spotify_id).await,
-      SpotifyAudioType::Podcast => self.resolve_episode_info(spotify_id).await,
-      SpotifyAudioType::NonPlayable => Err(anyhow!("Cannot resolve non-playable audio type")),
+  /// Retrieve the metadata for a `SpotifyId`, so that something can be shown even if that fails
+  async fn resolve_or_unknown(&self, spotify_id: SpotifyId) -> CurrentTrack {
+    match resolve_audio_info(&self.session, spotify_id).await {
+      Ok(current) => current,
+      Err(why) => {
+        warn!("Failed to resolve audio info: {:?}", why);
+        CurrentTrack::Unknown
+      }
     }
   }
 
-  /// Retrieve the metadata for a Spotify Track
-  async fn resolve_track_info(&self, spotify_id: SpotifyId) -> Result<CurrentTrack> {
-    let result = self
-      .session
-      .mercury()
-      .get(format!("hm://metadata/3/track/{}", spotify_id.to_base16()?))
-      .await
-      .map_err(|_| anyhow!("Mercury metadata request failed"))?;
+  /// Get a copy of the current playback info
+  async fn snapshot(&self) -> Option<PlaybackInfo> {
+    self.pbi.lock().await.clone()
+  }
+}
 
-    if result.status_code != 200 {
-      return Err(anyhow!("Mercury metadata request invalid status code"));
-    }
+/// The session configuration shared by both ways of logging in
+fn session_config() -> SessionConfig {
+  SessionConfig {
+    ap_port: Some(9999), // Force the use of ap.spotify.com, which has the lowest latency
+    ..Default::default()
+  }
+}
 
-    let message = match result.payload.get(0) {
-      Some(v) => v,
-      None => return Err(anyhow!("Mercury metadata request invalid payload")),
-    };
+/// Retrieve the metadata for a `SpotifyId`
+async fn resolve_audio_info(session: &Session, spotify_id: SpotifyId) -> Result<CurrentTrack> {
+  match spotify_id.audio_type {
+    SpotifyAudioType::Track => resolve_track_info(session, spotify_id).await,
+    SpotifyAudioType::Podcast => resolve_episode_info(session, spotify_id).await,
+    // Local files have no metadata on Spotify's side
+    SpotifyAudioType::NonPlayable => Ok(CurrentTrack::Unknown),
+  }
+}
 
-    let proto_track = Track::parse_from_bytes(message)?;
+/// Retrieve the metadata for a Spotify Track
+async fn resolve_track_info(session: &Session, spotify_id: SpotifyId) -> Result<CurrentTrack> {
+  let result = session
+    .mercury()
+    .get(format!("hm://metadata/3/track/{}", spotify_id.to_base16()?))
+    .await
+    .map_err(|_| anyhow!("Mercury metadata request failed"))?;
 
-    Ok(CurrentTrack::Track(proto_track))
+  if result.status_code != 200 {
+    return Err(anyhow!("Mercury metadata request invalid status code"));
   }
 
-  /// Retrieve the metadata for a Spotify Podcast
-  async fn resolve_episode_info(&self, spotify_id: SpotifyId) -> Result<CurrentTrack> {
-    let result = self
-      .session
-      .mercury()
-      .get(format!(
-        "hm://metadata/3/episode/{}",
-        spotify_id.to_base16()?
-      ))
-      .await
-      .map_err(|_| anyhow!("Mercury metadata request failed"))?;
-
-    if result.status_code != 200 {
-      return Err(anyhow!("Mercury metadata request invalid status code"));
-    }
+  let message = match result.payload.get(0) {
+    Some(v) => v,
+    None => return Err(anyhow!("Mercury metadata request invalid payload")),
+  };
 
-    let message = match result.payload.get(0) {
-      Some(v) => v,
-      None => return Err(anyhow!("Mercury metadata request invalid payload")),
-    };
+  let proto_track = Track::parse_from_bytes(message)?;
 
-    let proto_episode = Episode::parse_from_bytes(message)?;
+  Ok(CurrentTrack::Track(proto_track))
+}
 
-    Ok(CurrentTrack::Episode(proto_episode))
+/// Retrieve the metadata for a Spotify Podcast
+async fn resolve_episode_info(session: &Session, spotify_id: SpotifyId) -> Result<CurrentTrack> {
+  let result = session
+    .mercury()
+    .get(format!(
+      "hm://metadata/3/episode/{}",
+      spotify_id.to_base16()?
+    ))
+    .await
+    .map_err(|_| anyhow!("Mercury metadata request failed"))?;
+
+  if result.status_code != 200 {
+    return Err(anyhow!("Mercury metadata request invalid status code"));
   }
+
+  let message = match result.payload.get(0) {
+    Some(v) => v,
+    None => return Err(anyhow!("Mercury metadata request invalid payload")),
+  };
+
+  let proto_episode = Episode::parse_from_bytes(message)?;
+
+  Ok(CurrentTrack::Episode(proto_episode))
+}
+
+fn percent_to_volume(percent: u8) -> u16 {
+  (percent.min(100) as u32 * u16::MAX as u32 / 100) as u16
+}
+
+fn volume_to_percent(volume: u16) -> u8 {
+  ((volume as u32 * 100 + u16::MAX as u32 / 2) / u16::MAX as u32) as u8
 }
 
 impl Drop for PlayerTask {
   fn drop(&mut self) {
     log::trace!("drop PlayerTask");
 
-    self.track.stop().ok();
+    let stats = self.stream.stats();
+    log::debug!(
+      "Stream finished with {} underruns and {} blocked writes",
+      stats.underruns,
+      stats.blocked_writes
+    );
+
+    if let Some(handle) = self.play_mode_refresh.take() {
+      handle.abort();
+    }
+
+    // The track belongs to the session, which stops it once it no longer needs it
     self.spirc.shutdown();
     self.session.shutdown();
-    self.stream.flush().ok();
+
+    // Audio still buffered keeps playing while the session reconnects
+    if !self.session_lost {
+      self.stream.flush().ok();
+    }
   }
 }
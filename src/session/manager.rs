@@ -189,6 +189,12 @@ impl SessionManager {
     self.0.read().await.get_active_session_count().await
   }
 
+  /// Get all sessions
+  #[cfg(feature = "stats")]
+  pub async fn sessions(&self) -> Vec<SpoticordSession> {
+    self.0.read().await.sessions()
+  }
+
   /// Tell all sessions to instantly shut down
   pub async fn shutdown(&self) {
     let sessions = self.0.read().await.sessions();
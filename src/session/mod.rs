@@ -3,35 +3,52 @@ pub mod pbi;
 
 use self::{
   manager::{SessionCreateError, SessionManager},
-  pbi::PlaybackInfo,
+  pbi::{CurrentTrack, PlaybackInfo},
 };
 use crate::{
-  audio::stream::Stream,
-  consts::DISCONNECT_TIME,
-  database::{Database, DatabaseError},
-  player::{Player, PlayerEvent},
-  utils::embed::Status,
+  audio::{
+    channels::ChannelMix,
+    eq::EqPreset,
+    settings::AudioSettings,
+    stream::Stream,
+    telemetry::{AudioTelemetry, Telemetry},
+    OutputMode,
+  },
+  consts::{
+    DISCONNECT_TIME, RECONNECT_ATTEMPTS, RECONNECT_DELAY, TOKEN_REFRESH_INTERVAL,
+    TOKEN_REFRESH_MARGIN,
+  },
+  database::{Database, DatabaseError, GuildSettings},
+  player::{bitrate::BitrateSetting, Connection, Player, PlayerEvent},
+  utils::{
+    self,
+    embed::Status,
+    spotify::{self, RepeatMode},
+  },
 };
+use librespot::core::spotify_id::SpotifyId;
 use log::*;
 use reqwest::StatusCode;
 use serenity::{
   async_trait,
   http::Http,
-  model::prelude::{ChannelId, GuildId, UserId},
+  model::prelude::{Channel, ChannelId, GuildId, UserId},
   prelude::{Context, RwLock},
 };
 use songbird::{
   create_player,
-  input::{Codec, Container, Input, Reader},
+  input::{codec::OpusDecoderState, Codec, Container, Input, Reader},
   tracks::TrackHandle,
   Call, Event, EventContext, EventHandler,
 };
 use std::{
+  future::Future,
   ops::{Deref, DerefMut},
+  pin::Pin,
   sync::Arc,
   time::Duration,
 };
-use tokio::sync::{Mutex, RwLockReadGuard, RwLockWriteGuard};
+use tokio::sync::{broadcast::error::RecvError, Mutex, RwLockReadGuard, RwLockWriteGuard};
 
 #[derive(Clone)]
 pub struct SpoticordSession(Arc<RwLock<InnerSpoticordSession>>);
@@ -42,6 +59,9 @@ struct InnerSpoticordSession {
   channel_id: ChannelId,
   text_channel_id: ChannelId,
 
+  /// The bitrate (in bps) of the voice channel, used to pick the Spotify bitrate automatically
+  channel_bitrate: Option<u64>,
+
   http: Arc<Http>,
 
   session_manager: SessionManager,
@@ -49,9 +69,14 @@ struct InnerSpoticordSession {
   call: Arc<Mutex<Call>>,
   track: Option<TrackHandle>,
   player: Option<Player>,
+  audio_settings: AudioSettings,
+  telemetry: Telemetry,
 
   disconnect_handle: Option<tokio::task::JoinHandle<()>>,
 
+  /// Renews the access token of the owner while the player is running
+  token_refresher: Option<tokio::task::JoinHandle<()>>,
+
   /// Whether the session has been disconnected
   /// If this is true then this instance should no longer be used and dropped
   disconnected: bool,
@@ -75,6 +100,15 @@ impl SpoticordSession {
     // Join the voice channel
     let songbird = songbird::get(ctx).await.expect("to be present").clone();
 
+    let channel_bitrate = match channel_id.to_channel(ctx).await {
+      Ok(Channel::Guild(channel)) => channel.bitrate,
+      Ok(_) => None,
+      Err(why) => {
+        warn!("Failed to get voice channel: {:?}", why);
+        None
+      }
+    };
+
     let (call, result) = songbird.join(guild_id, channel_id).await;
 
     if let Err(why) = result {
@@ -87,12 +121,16 @@ impl SpoticordSession {
       guild_id,
       channel_id,
       text_channel_id,
+      channel_bitrate,
       http: ctx.http.clone(),
       session_manager: session_manager.clone(),
       call: call.clone(),
       track: None,
       player: None,
+      audio_settings: AudioSettings::new(),
+      telemetry: Telemetry::new(),
       disconnect_handle: None,
+      token_refresher: None,
       disconnected: false,
     };
 
@@ -171,47 +209,131 @@ impl SpoticordSession {
     }
   }
 
-  async fn create_player(&mut self, ctx: &Context) -> Result<(), SessionCreateError> {
-    let owner_id = match self.owner().await {
-      Some(owner_id) => owner_id,
-      None => return Err(SessionCreateError::NoOwner),
-    };
+  /// Seek to a position (in milliseconds) in the current track
+  pub async fn seek(&self, position_ms: u32) {
+    if let Some(ref player) = self.acquire_read().await.player {
+      player.seek(position_ms).await;
+    }
+  }
 
-    let data = ctx.data.read().await;
-    let database = data.get::<Database>().expect("to contain a value");
+  /// Turn shuffling on or off
+  pub async fn set_shuffle(&self, shuffle: bool) {
+    if let Some(ref player) = self.acquire_read().await.player {
+      player.set_shuffle(shuffle).await;
+    }
+  }
 
-    let token = match database.get_access_token(owner_id.to_string()).await {
-      Ok(token) => token,
-      Err(why) => {
-        return match why {
-          DatabaseError::InvalidStatusCode(StatusCode::NOT_FOUND) => {
-            Err(SessionCreateError::NoSpotify)
-          }
-          DatabaseError::InvalidStatusCode(StatusCode::BAD_REQUEST) => {
-            Err(SessionCreateError::SpotifyExpired)
+  /// Change the repeat mode
+  pub async fn set_repeat(&self, repeat: RepeatMode) {
+    if let Some(ref player) = self.acquire_read().await.player {
+      player.set_repeat(repeat).await;
+    }
+  }
+
+  /// Change the volume (in percent) of the current player
+  pub async fn set_volume(&self, volume: u8) {
+    if let Some(ref player) = self.acquire_read().await.player {
+      player.set_volume(volume);
+    }
+  }
+
+  /// Get the volume (in percent) of the current player
+  pub async fn volume(&self) -> Option<u8> {
+    self
+      .acquire_read()
+      .await
+      .player
+      .as_ref()
+      .map(|player| player.volume())
+  }
+
+  /// Get the Spotify Connect device ID of the current player
+  pub async fn device_id(&self) -> Option<String> {
+    self
+      .acquire_read()
+      .await
+      .player
+      .as_ref()
+      .map(|player| player.device_id().to_string())
+  }
+
+  /// Retrieve the metadata of upcoming tracks or episodes using the session of the current player
+  pub async fn resolve(&self, spotify_ids: &[SpotifyId]) -> Vec<CurrentTrack> {
+    // Don't keep the session locked while waiting on Spotify
+    let player = self.acquire_read().await.player.clone();
+
+    match player {
+      Some(player) => player.resolve(spotify_ids).await,
+      None => vec![],
+    }
+  }
+
+  /// Change the equalizer preset of the current (and any future) player
+  pub async fn set_eq_preset(&self, preset: EqPreset) {
+    self
+      .acquire_read()
+      .await
+      .audio_settings
+      .set_eq_preset(preset);
+  }
+
+  /// Change the channel mix of the current (and any future) player
+  pub async fn set_channel_mix(&self, mix: ChannelMix) {
+    self
+      .acquire_read()
+      .await
+      .audio_settings
+      .set_channel_mix(mix);
+  }
+
+  /// Change the playback speed of podcast episodes, for the current (and any future) player
+  pub async fn set_speed(&self, speed: f32) {
+    let inner = self.acquire_read().await;
+
+    inner.audio_settings.set_speed(speed);
+
+    if let Some(ref player) = inner.player {
+      player.set_speed(speed).await;
+    }
+  }
+
+  async fn create_player(&mut self, ctx: &Context) -> Result<(), SessionCreateError> {
+    let database = ctx
+      .data
+      .read()
+      .await
+      .get::<Database>()
+      .expect("to contain a value")
+      .clone();
+
+    // Create stream
+    let telemetry = self.acquire_read().await.telemetry.clone();
+    let (stream, codec, container) = match OutputMode::from_env() {
+      OutputMode::Pcm => (Stream::new(telemetry), Codec::FloatPcm, Container::Raw),
+      OutputMode::Opus => {
+        let decoder = match OpusDecoderState::new() {
+          Ok(decoder) => decoder,
+          Err(why) => {
+            error!("Failed to create Opus decoder: {:?}", why);
+
+            return Err(SessionCreateError::PlayerStartError);
           }
-          _ => Err(SessionCreateError::DatabaseError),
         };
-      }
-    };
 
-    let user = match database.get_user(owner_id.to_string()).await {
-      Ok(user) => user,
-      Err(why) => {
-        error!("Failed to get user: {:?}", why);
-        return Err(SessionCreateError::DatabaseError);
+        (
+          Stream::new_framed(telemetry),
+          Codec::Opus(decoder),
+          Container::Dca { first_frame: 0 },
+        )
       }
     };
 
-    // Create stream
-    let stream = Stream::new();
-
     // Create track (paused, fixes audio glitches)
     let (mut track, track_handle) = create_player(Input::new(
       true,
       Reader::Extension(Box::new(stream.clone())),
-      Codec::FloatPcm,
-      Container::Raw,
+      codec,
+      container,
       None,
     ));
     track.pause();
@@ -222,30 +344,176 @@ impl SpoticordSession {
     // Set call audio to track
     call.play_only(track);
 
-    let (player, mut rx) =
-      match Player::create(stream, &token, &user.device_name, track_handle.clone()).await {
-        Ok(v) => v,
-        Err(why) => {
-          error!("Failed to start the player: {:?}", why);
+    if let Err(why) = self
+      .start_player(database, stream, track_handle.clone())
+      .await
+    {
+      track_handle.stop().ok();
+
+      return Err(why);
+    }
 
-          return Err(SessionCreateError::PlayerStartError);
+    Ok(())
+  }
+
+  /// Start a Spotify player for the owner, which plays through an existing stream and track
+  async fn start_player(
+    &self,
+    database: Database,
+    stream: Stream,
+    track_handle: TrackHandle,
+  ) -> Result<(), SessionCreateError> {
+    let owner_id = match self.owner().await {
+      Some(owner_id) => owner_id,
+      None => return Err(SessionCreateError::NoOwner),
+    };
+
+    let user = match database.get_user(owner_id.to_string()).await {
+      Ok(user) => user,
+      Err(why) => {
+        error!("Failed to get user: {:?}", why);
+        return Err(SessionCreateError::DatabaseError);
+      }
+    };
+
+    let guild_settings = match database
+      .get_guild_settings(self.guild_id().await.to_string())
+      .await
+    {
+      Ok(settings) => settings,
+      Err(why) => {
+        warn!("Failed to get guild settings, using defaults: {:?}", why);
+        GuildSettings::default()
+      }
+    };
+
+    let bitrate = guild_settings
+      .bitrate
+      .unwrap_or_else(BitrateSetting::from_env)
+      .resolve(self.acquire_read().await.channel_bitrate);
+
+    let audio_settings = self.audio_settings().await;
+    audio_settings.set_eq_preset(guild_settings.eq_preset);
+    audio_settings.set_channel_mix(guild_settings.channels);
+
+    let stored = match database.get_user_credentials(owner_id.to_string()).await {
+      Ok(credentials) => Some(credentials),
+      Err(DatabaseError::InvalidStatusCode(StatusCode::NOT_FOUND)) => None,
+      Err(why) => {
+        warn!("Failed to get stored credentials: {:?}", why);
+        None
+      }
+    };
+
+    // Stored credentials skip the access token, and with it a Web API request
+    let mut connection = None;
+
+    if let Some(credentials) = stored.clone() {
+      match Connection::with_credentials(credentials).await {
+        Ok(v) => connection = Some(v),
+        Err(why) => warn!(
+          "Stored credentials were rejected, using the access token instead: {:?}",
+          why
+        ),
+      }
+    }
+
+    let connection = match connection {
+      Some(connection) => connection,
+      None => {
+        let token = match database.get_access_token(owner_id.to_string()).await {
+          Ok(token) => token,
+          Err(why) => {
+            return match why {
+              DatabaseError::InvalidStatusCode(StatusCode::NOT_FOUND) => {
+                Err(SessionCreateError::NoSpotify)
+              }
+              DatabaseError::InvalidStatusCode(StatusCode::BAD_REQUEST) => {
+                Err(SessionCreateError::SpotifyExpired)
+              }
+              _ => Err(SessionCreateError::DatabaseError),
+            };
+          }
+        };
+
+        match Connection::with_token(token).await {
+          Ok(connection) => connection,
+          Err(why) => {
+            error!("Failed to connect to Spotify: {:?}", why);
+
+            return Err(SessionCreateError::PlayerStartError);
+          }
         }
-      };
+      }
+    };
+
+    // Without a token up front the player gets one from the token refresher
+    let renew_token = !connection.has_token();
+
+    let (player, mut rx) = match Player::create(
+      stream.clone(),
+      connection,
+      &user,
+      track_handle.clone(),
+      audio_settings,
+      &guild_settings,
+      bitrate,
+    )
+    .await
+    {
+      Ok(v) => v,
+      Err(why) => {
+        error!("Failed to start the player: {:?}", why);
+
+        return Err(SessionCreateError::PlayerStartError);
+      }
+    };
+
+    // Store the credentials Spotify handed out, so the next connection can skip the access token
+    let stored = stored
+      .as_ref()
+      .map(|credentials| credentials.auth_data == player.credentials().auth_data)
+      .unwrap_or(false);
+
+    if !stored {
+      if let Err(why) = database
+        .update_user_credentials(owner_id.to_string(), player.credentials())
+        .await
+      {
+        error!("Failed to store credentials: {:?}", why);
+      }
+    }
 
     tokio::spawn({
       let session = self.clone();
+      let database = database.clone();
+      let track_handle = track_handle.clone();
 
       async move {
         loop {
           match rx.recv().await {
             Ok(event) => match event {
-              PlayerEvent::Pause => session.start_disconnect_timer().await,
-              PlayerEvent::Play => session.stop_disconnect_timer().await,
+              PlayerEvent::Pause(_) => session.start_disconnect_timer().await,
+              PlayerEvent::Play(_) => session.stop_disconnect_timer().await,
+              PlayerEvent::SessionLost(pbi) => {
+                // The old player leaves its stream and track alone, so a new one can take them over
+                session.reconnect(database, stream, track_handle, pbi).await;
+                break;
+              }
+              PlayerEvent::Unavailable(spotify_id, _) => {
+                session.track_unavailable(spotify_id).await
+              }
+              PlayerEvent::Failed(reason) => session.player_failed(&reason).await,
               PlayerEvent::Stopped => {
                 session.player_stopped().await;
                 break;
               }
+              _ => {}
             },
+            // Missing a few events is fine, as long as the player is still around
+            Err(RecvError::Lagged(count)) => {
+              warn!("Missed {count} player events");
+            }
             Err(why) => {
               error!("Communication with player abruptly ended: {why}");
               session.player_stopped().await;
@@ -257,16 +525,184 @@ impl SpoticordSession {
       }
     });
 
-    // Start DC timer by default, as automatic device switching is now gone
+    // Start DC timer by default, playback may not be transferred to the device
     self.start_disconnect_timer().await;
 
     let mut inner = self.acquire_write().await;
     inner.track = Some(track_handle);
     inner.player = Some(player);
 
+    // Started after the player is in place, as it may hand the player a token right away
+    let token_refresher = self.start_token_refresher(database, owner_id, renew_token);
+
+    if let Some(handle) = inner.token_refresher.replace(token_refresher) {
+      handle.abort();
+    }
+
     Ok(())
   }
 
+  /// Renew the access token of the owner ahead of time, and hand it to the player
+  ///
+  /// The database only hands out a new token once the old one is about to expire, so until it does
+  /// this keeps asking every `TOKEN_REFRESH_INTERVAL` seconds. With `renew_now` set the first token
+  /// is handed over right away, for players that logged in without one.
+  fn start_token_refresher(
+    &self,
+    database: Database,
+    owner_id: UserId,
+    mut renew_now: bool,
+  ) -> tokio::task::JoinHandle<()> {
+    let session = self.clone();
+
+    tokio::spawn(async move {
+      loop {
+        if !renew_now {
+          // `expires` is a timestamp in milliseconds
+          let wait = match database.get_user_account(owner_id.to_string()).await {
+            Ok(account) => Duration::from_millis(
+              account
+                .expires
+                .saturating_sub(TOKEN_REFRESH_MARGIN * 1000)
+                .saturating_sub(utils::get_time_ms() as u64),
+            ),
+            Err(why) => {
+              error!("Failed to get Spotify account: {:?}", why);
+              Duration::ZERO
+            }
+          };
+
+          tokio::time::sleep(wait.max(Duration::from_secs(TOKEN_REFRESH_INTERVAL))).await;
+        }
+
+        renew_now = false;
+
+        let token = match database.get_access_token(owner_id.to_string()).await {
+          Ok(token) => token,
+          Err(why) => {
+            error!("Failed to renew access token: {:?}", why);
+            continue;
+          }
+        };
+
+        match session.acquire_read().await.player {
+          Some(ref player) => player.set_token(token),
+          None => break,
+        }
+      }
+    })
+  }
+
+  /// Called when the connection to Spotify was lost, replaces the player while staying in the call
+  ///
+  /// `pbi` is the playback info the old player had when the connection was lost. Only after all
+  /// attempts failed is the player stopped and the text channel notified.
+  fn reconnect(
+    &self,
+    database: Database,
+    stream: Stream,
+    track_handle: TrackHandle,
+    pbi: Option<PlaybackInfo>,
+  ) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+    Box::pin(async move {
+      let guild_id = self.guild_id().await;
+      let owner_id = self.owner().await;
+
+      // Remember where playback was now, the position keeps advancing while reconnecting
+      let resume = pbi.map(|pbi| {
+        let position_ms = match pbi.duration_ms {
+          0 => pbi.get_position(),
+          duration_ms => pbi.get_position().min(duration_ms),
+        };
+
+        (position_ms, pbi.is_playing)
+      });
+
+      warn!(
+        "[{}] Lost the connection to Spotify, reconnecting",
+        guild_id
+      );
+
+      // The old player can no longer be controlled
+      if let Some(player) = self.acquire_write().await.player.take() {
+        player.shutdown();
+      }
+
+      let mut delay = Duration::from_secs(RECONNECT_DELAY);
+
+      for attempt in 1..=RECONNECT_ATTEMPTS {
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+
+        // Stop if the session was left or taken over in the meantime
+        {
+          let inner = self.acquire_read().await;
+
+          if inner.disconnected || inner.owner != owner_id || inner.player.is_some() {
+            return;
+          }
+        }
+
+        match self
+          .start_player(database.clone(), stream.clone(), track_handle.clone())
+          .await
+        {
+          Ok(()) => {
+            info!("[{}] Reconnected to Spotify", guild_id);
+
+            self.resume_playback(&database, resume).await;
+            return;
+          }
+          Err(why) => warn!(
+            "[{}] Reconnect attempt {} of {} failed: {:?}",
+            guild_id, attempt, RECONNECT_ATTEMPTS, why
+          ),
+        }
+      }
+
+      self
+        .player_failed("The connection to Spotify was lost")
+        .await;
+      self.player_stopped().await;
+    })
+  }
+
+  /// Continue what was playing before the player was replaced, on the new player
+  ///
+  /// `resume` holds the position (in milliseconds) to continue from, and whether it was playing.
+  async fn resume_playback(&self, database: &Database, resume: Option<(u32, bool)>) {
+    let (Some(owner_id), Some(device_id)) = (self.owner().await, self.device_id().await) else {
+      return;
+    };
+
+    let token = match database.get_access_token(owner_id.to_string()).await {
+      Ok(token) => token,
+      Err(why) => {
+        error!("Failed to get access token: {:?}", why);
+        return;
+      }
+    };
+
+    if let Err(why) = spotify::transfer_playback(&token, &device_id).await {
+      error!("Failed to resume playback: {:?}", why);
+      return;
+    }
+
+    let Some((position_ms, is_playing)) = resume else {
+      return;
+    };
+
+    if let Err(why) = spotify::seek(&token, &device_id, position_ms).await {
+      error!("Failed to restore playback position: {:?}", why);
+    }
+
+    if is_playing {
+      if let Some(ref player) = self.acquire_read().await.player {
+        player.play();
+      }
+    }
+  }
+
   /// Called when the player must stop, but not leave the call
   async fn player_stopped(&self) {
     let mut inner = self.acquire_write().await;
@@ -287,6 +723,10 @@ impl SpoticordSession {
       player.shutdown();
     }
 
+    if let Some(handle) = inner.token_refresher.take() {
+      handle.abort();
+    }
+
     // Unlock to prevent deadlock in start_disconnect_timer
     drop(inner);
 
@@ -294,6 +734,59 @@ impl SpoticordSession {
     self.start_disconnect_timer().await;
   }
 
+  /// Called when the player ran into an error, the player itself is stopped right after
+  async fn player_failed(&self, reason: &str) {
+    let inner = self.acquire_read().await;
+
+    error!("[{}] Player failed: {}", inner.guild_id, reason);
+
+    if let Err(why) = inner
+      .text_channel_id
+      .send_message(&inner.http, |message| {
+        message.embed(|embed| {
+          embed.title("Playback stopped");
+          embed.description(format!(
+            "Something went wrong while playing music: {reason}\nUse `/join` to start playing again."
+          ));
+          embed.color(Status::Error as u64);
+
+          embed
+        })
+      })
+      .await
+    {
+      error!("Failed to send player failure message: {:?}", why);
+    }
+  }
+
+  /// Called when Spotify skipped a track that can't be played, like one restricted to other regions
+  async fn track_unavailable(&self, spotify_id: SpotifyId) {
+    let name = match self.resolve(&[spotify_id]).await.pop() {
+      Some(CurrentTrack::Unknown) | None => "A track".to_string(),
+      Some(track) => format!("**{} - {}**", track.artists(), track.name()),
+    };
+
+    let inner = self.acquire_read().await;
+
+    if let Err(why) = inner
+      .text_channel_id
+      .send_message(&inner.http, |message| {
+        message.embed(|embed| {
+          embed.title("Track skipped");
+          embed.description(format!(
+            "{name} is not available on Spotify, possibly not in your region, and was skipped."
+          ));
+          embed.color(Status::Warning as u64);
+
+          embed
+        })
+      })
+      .await
+    {
+      error!("Failed to send unavailable track message: {:?}", why);
+    }
+  }
+
   // Disconnect from voice channel and remove session from manager
   pub async fn disconnect(&self) {
     info!(
@@ -423,6 +916,17 @@ impl SpoticordSession {
     self.acquire_read().await.text_channel_id
   }
 
+  /// Get the audio pipeline counters of this session, covering every player it has had
+  #[cfg_attr(not(feature = "stats"), allow(dead_code))]
+  pub async fn audio_telemetry(&self) -> AudioTelemetry {
+    self.acquire_read().await.telemetry.snapshot()
+  }
+
+  /// Get the audio settings
+  pub async fn audio_settings(&self) -> AudioSettings {
+    self.acquire_read().await.audio_settings.clone()
+  }
+
   /// Get the playback info
   pub async fn playback_info(&self) -> Option<PlaybackInfo> {
     let handle = self.acquire_read().await;
@@ -493,6 +997,10 @@ impl InnerSpoticordSession {
       player.shutdown();
     }
 
+    if let Some(handle) = self.token_refresher.take() {
+      handle.abort();
+    }
+
     self.disconnected = true;
     self
       .session_manager
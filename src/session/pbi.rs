@@ -1,11 +1,11 @@
 use librespot::{
-  core::spotify_id::SpotifyId,
+  core::spotify_id::{SpotifyAudioType, SpotifyId},
   protocol::metadata::{Episode, Track},
 };
 
-use crate::utils;
+use crate::utils::{self, spotify::RepeatMode};
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct PlaybackInfo {
   last_updated: u128,
   position_ms: u32,
@@ -15,12 +15,57 @@ pub struct PlaybackInfo {
 
   pub duration_ms: u32,
   pub is_playing: bool,
+
+  /// The speed at which episodes are played
+  pub speed: f32,
+
+  pub shuffle: bool,
+  pub repeat: RepeatMode,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum CurrentTrack {
   Track(Track),
   Episode(Episode),
+
+  /// A local file, or something of which the metadata could not be retrieved
+  Unknown,
+}
+
+impl CurrentTrack {
+  /// Get the name of the track or episode
+  pub fn name(&self) -> String {
+    match self {
+      CurrentTrack::Track(track) => track.get_name().to_string(),
+      CurrentTrack::Episode(episode) => episode.get_name().to_string(),
+      CurrentTrack::Unknown => "Unknown track".to_string(),
+    }
+  }
+
+  /// Get the artist(s) or show name of the track
+  pub fn artists(&self) -> String {
+    match self {
+      CurrentTrack::Track(track) => track
+        .get_artist()
+        .iter()
+        .map(|a| a.get_name().to_string())
+        .collect::<Vec<_>>()
+        .join(", "),
+      CurrentTrack::Episode(episode) => episode.get_show().get_name().to_string(),
+      CurrentTrack::Unknown => "Unknown artist".to_string(),
+    }
+  }
+
+  /// Get the duration of the track or episode
+  pub fn duration_ms(&self) -> u32 {
+    let duration = match self {
+      CurrentTrack::Track(track) => track.get_duration(),
+      CurrentTrack::Episode(episode) => episode.get_duration(),
+      CurrentTrack::Unknown => 0,
+    };
+
+    duration.max(0) as u32
+  }
 }
 
 impl PlaybackInfo {
@@ -31,6 +76,7 @@ impl PlaybackInfo {
     is_playing: bool,
     track: CurrentTrack,
     spotify_id: SpotifyId,
+    speed: f32,
   ) -> Self {
     Self {
       last_updated: utils::get_time_ms(),
@@ -39,6 +85,9 @@ impl PlaybackInfo {
       duration_ms,
       position_ms,
       is_playing,
+      speed,
+      shuffle: false,
+      repeat: RepeatMode::Off,
     }
   }
 
@@ -51,19 +100,41 @@ impl PlaybackInfo {
     self.last_updated = utils::get_time_ms();
   }
 
+  /// Move to another position, clamped to the duration if it is known
+  pub fn seek(&mut self, position_ms: u32) {
+    self.position_ms = match self.duration_ms {
+      0 => position_ms,
+      duration_ms => position_ms.min(duration_ms),
+    };
+    self.last_updated = utils::get_time_ms();
+  }
+
   /// Update spotify id, track and episode
   pub fn update_track(&mut self, spotify_id: SpotifyId, track: CurrentTrack) {
     self.spotify_id = spotify_id;
     self.track = track;
   }
 
+  /// Update the episode playback speed, keeping the position that was reached at the old speed
+  pub fn set_speed(&mut self, speed: f32) {
+    self.position_ms = self.get_position();
+    self.last_updated = utils::get_time_ms();
+    self.speed = speed;
+  }
+
   /// Get the current playback position
   pub fn get_position(&self) -> u32 {
     if self.is_playing {
       let now = utils::get_time_ms();
       let diff = now - self.last_updated;
 
-      self.position_ms + diff as u32
+      // Episodes that are sped up also move through the episode faster
+      let speed = match self.track {
+        CurrentTrack::Episode(_) => self.speed as f64,
+        _ => 1.0,
+      };
+
+      self.position_ms + (diff as f64 * speed) as u32
     } else {
       self.position_ms
     }
@@ -71,23 +142,12 @@ impl PlaybackInfo {
 
   /// Get the name of the track or episode
   pub fn get_name(&self) -> String {
-    match &self.track {
-      CurrentTrack::Track(track) => track.get_name().to_string(),
-      CurrentTrack::Episode(episode) => episode.get_name().to_string(),
-    }
+    self.track.name()
   }
 
   /// Get the artist(s) or show name of the current track
   pub fn get_artists(&self) -> String {
-    match &self.track {
-      CurrentTrack::Track(track) => track
-        .get_artist()
-        .iter()
-        .map(|a| a.get_name().to_string())
-        .collect::<Vec<_>>()
-        .join(", "),
-      CurrentTrack::Episode(episode) => episode.get_show().get_name().to_string(),
-    }
+    self.track.artists()
   }
 
   /// Get the album art url
@@ -113,6 +173,7 @@ impl PlaybackInfo {
           .map(|image| image.get_file_id())
           .map(hex::encode)
       }
+      CurrentTrack::Unknown => None,
     };
 
     file_id.map(|id| format!("https://i.scdn.co/image/{id}"))
@@ -124,6 +185,10 @@ impl PlaybackInfo {
     match &self.track {
       CurrentTrack::Track(_) => "track".to_string(),
       CurrentTrack::Episode(_) => "episode".to_string(),
+      CurrentTrack::Unknown => match self.spotify_id.audio_type {
+        SpotifyAudioType::Podcast => "episode".to_string(),
+        _ => "track".to_string(),
+      },
     }
   }
 
@@ -137,6 +202,7 @@ impl PlaybackInfo {
         .find(|id| id.get_typ() == "spotify")
         .map(|v| v.get_id()),
       CurrentTrack::Episode(episode) => Some(episode.get_external_url()),
+      CurrentTrack::Unknown => None,
     }
   }
 }
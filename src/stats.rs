@@ -1,4 +1,10 @@
 use redis::{Client, Commands, RedisResult as Result};
+use serenity::model::prelude::GuildId;
+
+use crate::audio::telemetry::AudioTelemetry;
+
+/// How long the audio telemetry of a session is kept after it was last updated, in seconds
+const AUDIO_TELEMETRY_TTL: usize = 120;
 
 #[derive(Clone)]
 pub struct StatsManager {
@@ -23,4 +29,32 @@ impl StatsManager {
 
     con.set("sc-bot-active-servers", count.to_string())
   }
+
+  pub fn set_audio_telemetry(&self, guild_id: GuildId, telemetry: &AudioTelemetry) -> Result<()> {
+    let mut con = self.redis.get_connection()?;
+    let key = format!("sc-bot-audio:{guild_id}");
+
+    let fields = [
+      ("samples_written", telemetry.samples_written),
+      ("bytes_read", telemetry.bytes_read),
+      ("zero_fill_reads", telemetry.zero_fill_reads),
+      (
+        "blocked_write_us",
+        telemetry.blocked_write_time.as_micros() as u64,
+      ),
+      ("resample_us", telemetry.resample_time.as_micros() as u64),
+      ("resample_count", telemetry.resample_count),
+      ("starts", telemetry.starts),
+      ("stops", telemetry.stops),
+      ("buffer_target_ms", telemetry.buffer_target_ms),
+    ];
+
+    // Expire the key, so that sessions that have ended disappear on their own
+    redis::pipe()
+      .hset_multiple(&key, &fields)
+      .ignore()
+      .expire(&key, AUDIO_TELEMETRY_TTL)
+      .ignore()
+      .query(&mut con)
+  }
 }
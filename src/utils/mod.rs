@@ -4,6 +4,9 @@ pub mod discord;
 pub mod embed;
 pub mod spotify;
 
+#[cfg(test)]
+mod tests;
+
 pub fn get_time() -> u64 {
   let now = SystemTime::now();
   let since_the_epoch = now.duration_since(UNIX_EPOCH).expect("Time went backwards");
@@ -35,3 +38,22 @@ pub fn time_to_str(time: u32) -> String {
     format!("{}s", time)
   }
 }
+
+/// Parse a time in seconds from `ss`, `mm:ss` or `hh:mm:ss`
+pub fn str_to_time(value: &str) -> Option<u32> {
+  let parts = value
+    .trim()
+    .split(':')
+    .map(|part| part.parse::<u32>().ok())
+    .collect::<Option<Vec<_>>>()?;
+
+  match parts.as_slice() {
+    [seconds] => Some(*seconds),
+    // Anything that doesn't fit is far longer than any track, so it is treated as invalid
+    [minutes, seconds] if *seconds < 60 => minutes.checked_mul(60)?.checked_add(*seconds),
+    [hours, minutes, seconds] if *minutes < 60 && *seconds < 60 => {
+      hours.checked_mul(3600)?.checked_add(minutes * 60 + seconds)
+    }
+    _ => None,
+  }
+}
@@ -1,6 +1,10 @@
+use std::{str::FromStr, time::Duration};
+
 use anyhow::{anyhow, Result};
+use librespot::core::spotify_id::{SpotifyAudioType, SpotifyId};
 use log::{error, trace};
-use serde_json::Value;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
 
 pub async fn get_username(token: impl Into<String>) -> Result<String> {
   let token = token.into();
@@ -52,3 +56,504 @@ pub async fn get_username(token: impl Into<String>) -> Result<String> {
     return Err(anyhow!("Failed to parse body: Invalid body received"));
   }
 }
+
+/// The kinds of Spotify items that can be played
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ItemKind {
+  Track,
+  Album,
+  Playlist,
+  Episode,
+  Show,
+}
+
+impl ItemKind {
+  fn parse(kind: &str) -> Option<Self> {
+    match kind {
+      "track" => Some(Self::Track),
+      "album" => Some(Self::Album),
+      "playlist" => Some(Self::Playlist),
+      "episode" => Some(Self::Episode),
+      "show" => Some(Self::Show),
+      _ => None,
+    }
+  }
+
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Self::Track => "track",
+      Self::Album => "album",
+      Self::Playlist => "playlist",
+      Self::Episode => "episode",
+      Self::Show => "show",
+    }
+  }
+
+  /// The human readable name of the kind
+  pub fn name(&self) -> &'static str {
+    match self {
+      Self::Track => "Track",
+      Self::Album => "Album",
+      Self::Playlist => "Playlist",
+      Self::Episode => "Episode",
+      Self::Show => "Show",
+    }
+  }
+
+  /// Whether the item is a single track or episode, instead of a collection of them
+  pub fn is_playable(&self) -> bool {
+    matches!(self, Self::Track | Self::Episode)
+  }
+}
+
+/// A track, album, playlist, episode or show, parsed from a Spotify URI or link
+#[derive(Clone, Copy, Debug)]
+pub struct SpotifyItem {
+  pub kind: ItemKind,
+  pub id: SpotifyId,
+}
+
+impl SpotifyItem {
+  pub fn to_uri(&self) -> String {
+    format!(
+      "spotify:{}:{}",
+      self.kind.as_str(),
+      self.id.to_base62().unwrap_or_default()
+    )
+  }
+}
+
+impl FromStr for SpotifyItem {
+  type Err = anyhow::Error;
+
+  /// Parse `spotify:<kind>:<id>` URIs and `https://open.spotify.com/<kind>/<id>` links
+  fn from_str(value: &str) -> Result<Self> {
+    let value = value.trim();
+
+    let (kind, id) = if let Some(uri) = value.strip_prefix("spotify:") {
+      uri
+        .split_once(':')
+        .ok_or_else(|| anyhow!("Invalid Spotify URI"))?
+    } else {
+      let path = value
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .strip_prefix("open.spotify.com/")
+        .ok_or_else(|| anyhow!("Not a Spotify link"))?;
+
+      // Drop the query string, and the locale prefix some links have (e.g. `/intl-de/track/...`)
+      let path = path.split(['?', '#']).next().unwrap_or_default();
+      let mut segments = path
+        .split('/')
+        .filter(|segment| !segment.starts_with("intl-"));
+
+      match (segments.next(), segments.next()) {
+        (Some(kind), Some(id)) => (kind, id),
+        _ => return Err(anyhow!("Invalid Spotify link")),
+      }
+    };
+
+    let kind = ItemKind::parse(kind).ok_or_else(|| anyhow!("Unsupported item type: {kind}"))?;
+    let mut id = SpotifyId::from_base62(id).map_err(|_| anyhow!("Invalid Spotify ID: {id}"))?;
+
+    id.audio_type = match kind {
+      ItemKind::Track => SpotifyAudioType::Track,
+      ItemKind::Episode => SpotifyAudioType::Podcast,
+      _ => SpotifyAudioType::NonPlayable,
+    };
+
+    Ok(Self { kind, id })
+  }
+}
+
+/// Start playing an item on a Spotify Connect device, using the Web API
+pub async fn start_playback(
+  token: impl Into<String>,
+  device_id: &str,
+  item: &SpotifyItem,
+) -> Result<()> {
+  let token = token.into();
+  let client = reqwest::Client::new();
+
+  // Single tracks and episodes are played on their own, everything else is played as a context
+  let body = if item.kind.is_playable() {
+    json!({ "uris": [item.to_uri()] })
+  } else {
+    json!({ "context_uri": item.to_uri() })
+  };
+
+  let mut retries = 3;
+
+  loop {
+    let response = match client
+      .put("https://api.spotify.com/v1/me/player/play")
+      .query(&[("device_id", device_id)])
+      .bearer_auth(&token)
+      .json(&body)
+      .send()
+      .await
+    {
+      Ok(response) => response,
+      Err(why) => {
+        error!("Failed to start playback: {}", why);
+        return Err(why.into());
+      }
+    };
+
+    if response.status().as_u16() >= 500 && retries > 0 {
+      retries -= 1;
+      continue;
+    }
+
+    return match response.status() {
+      status if status.is_success() => Ok(()),
+      StatusCode::NOT_FOUND => Err(anyhow!(
+        "Spotify could not find the item or the device, please try again in a few seconds"
+      )),
+      StatusCode::FORBIDDEN => Err(anyhow!(
+        "Spotify refused to play this, which requires Spotify Premium"
+      )),
+      status => {
+        error!("Failed to start playback: {}", status);
+        Err(anyhow!(
+          "Failed to start playback: Invalid status code: {}",
+          status
+        ))
+      }
+    };
+  }
+}
+
+/// A single result of a Spotify search
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+  pub item: SpotifyItem,
+  pub name: String,
+
+  /// The artists of a track or album, or the owner of a playlist
+  pub creator: String,
+}
+
+/// Search Spotify for tracks, albums and playlists, returning at most `limit` results of each kind
+pub async fn search(
+  token: impl Into<String>,
+  query: &str,
+  limit: usize,
+) -> Result<Vec<SearchResult>> {
+  let token = token.into();
+  let client = reqwest::Client::new();
+
+  let mut retries = 3;
+
+  loop {
+    let response = match client
+      .get("https://api.spotify.com/v1/search")
+      .query(&[
+        ("q", query),
+        ("type", "track,album,playlist"),
+        ("limit", &limit.to_string()),
+      ])
+      .bearer_auth(&token)
+      .send()
+      .await
+    {
+      Ok(response) => response,
+      Err(why) => {
+        error!("Failed to search: {}", why);
+        return Err(why.into());
+      }
+    };
+
+    if response.status().as_u16() >= 500 && retries > 0 {
+      retries -= 1;
+      continue;
+    }
+
+    if response.status() != 200 {
+      error!("Failed to search: {}", response.status());
+      return Err(anyhow!(
+        "Failed to search: Invalid status code: {}",
+        response.status()
+      ));
+    }
+
+    let body: Value = match response.json().await {
+      Ok(body) => body,
+      Err(why) => {
+        error!("Failed to parse body: {}", why);
+        return Err(why.into());
+      }
+    };
+
+    let artists = |item: &Value| {
+      item["artists"]
+        .as_array()
+        .map(|artists| {
+          artists
+            .iter()
+            .filter_map(|artist| artist["name"].as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+        })
+        .unwrap_or_default()
+    };
+
+    let mut results = vec![];
+
+    for kind in ["tracks", "albums", "playlists"] {
+      let Some(items) = body[kind]["items"].as_array() else {
+        continue;
+      };
+
+      // Spotify sometimes returns `null` in place of items that are no longer available
+      for item in items.iter().filter(|item| !item.is_null()) {
+        let (Some(uri), Some(name)) = (item["uri"].as_str(), item["name"].as_str()) else {
+          continue;
+        };
+
+        let Ok(parsed) = uri.parse::<SpotifyItem>() else {
+          continue;
+        };
+
+        let creator = match parsed.kind {
+          ItemKind::Playlist => item["owner"]["display_name"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+          _ => artists(item),
+        };
+
+        results.push(SearchResult {
+          item: parsed,
+          name: name.to_string(),
+          creator,
+        });
+      }
+    }
+
+    return Ok(results);
+  }
+}
+
+/// Move the current playback of a user to a Spotify Connect device, using the Web API
+///
+/// A device only shows up in the Web API some time after it connected, so this waits for it first.
+pub async fn transfer_playback(token: impl Into<String>, device_id: &str) -> Result<()> {
+  const ATTEMPTS: usize = 10;
+  const INTERVAL: Duration = Duration::from_millis(500);
+
+  let token = token.into();
+  let client = reqwest::Client::new();
+
+  let mut registered = false;
+
+  for _ in 0..ATTEMPTS {
+    let response = client
+      .get("https://api.spotify.com/v1/me/player/devices")
+      .bearer_auth(&token)
+      .send()
+      .await?;
+
+    if response.status() == 200 {
+      let body: Value = response.json().await?;
+
+      registered = body["devices"]
+        .as_array()
+        .map(|devices| devices.iter().any(|device| device["id"] == device_id))
+        .unwrap_or(false);
+
+      if registered {
+        break;
+      }
+    }
+
+    tokio::time::sleep(INTERVAL).await;
+  }
+
+  if !registered {
+    return Err(anyhow!(
+      "The device was not registered with Spotify in time"
+    ));
+  }
+
+  let response = client
+    .put("https://api.spotify.com/v1/me/player")
+    .bearer_auth(&token)
+    .json(&json!({ "device_ids": [device_id] }))
+    .send()
+    .await?;
+
+  match response.status() {
+    status if status.is_success() => Ok(()),
+    status => {
+      error!("Failed to transfer playback: {}", status);
+      Err(anyhow!(
+        "Failed to transfer playback: Invalid status code: {}",
+        status
+      ))
+    }
+  }
+}
+
+/// Seek to a position in the track that is playing on a Spotify Connect device, using the Web API
+pub async fn seek(token: impl Into<String>, device_id: &str, position_ms: u32) -> Result<()> {
+  control_player(
+    token,
+    "seek",
+    "position_ms",
+    &position_ms.to_string(),
+    device_id,
+  )
+  .await
+}
+
+/// The repeat modes of the Spotify player
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RepeatMode {
+  #[default]
+  Off,
+  Context,
+  Track,
+}
+
+impl RepeatMode {
+  pub const ALL: [RepeatMode; 3] = [Self::Off, Self::Context, Self::Track];
+
+  /// The identifier used by the Web API and in commands
+  pub fn id(&self) -> &'static str {
+    match self {
+      Self::Off => "off",
+      Self::Context => "context",
+      Self::Track => "track",
+    }
+  }
+
+  /// The human readable name of the mode
+  pub fn name(&self) -> &'static str {
+    match self {
+      Self::Off => "Off",
+      Self::Context => "All",
+      Self::Track => "Track",
+    }
+  }
+
+  /// The mode that comes after this one when cycling through them
+  pub fn next(&self) -> Self {
+    match self {
+      Self::Off => Self::Context,
+      Self::Context => Self::Track,
+      Self::Track => Self::Off,
+    }
+  }
+}
+
+impl FromStr for RepeatMode {
+  type Err = anyhow::Error;
+
+  fn from_str(value: &str) -> Result<Self> {
+    Self::ALL
+      .into_iter()
+      .find(|mode| mode.id() == value)
+      .ok_or_else(|| anyhow!("Unknown repeat mode: {value}"))
+  }
+}
+
+/// Turn shuffling on or off on a Spotify Connect device, using the Web API
+pub async fn set_shuffle(token: impl Into<String>, device_id: &str, shuffle: bool) -> Result<()> {
+  control_player(token, "shuffle", "state", &shuffle.to_string(), device_id).await
+}
+
+/// Change the repeat mode of a Spotify Connect device, using the Web API
+pub async fn set_repeat(
+  token: impl Into<String>,
+  device_id: &str,
+  repeat: RepeatMode,
+) -> Result<()> {
+  control_player(token, "repeat", "state", repeat.id(), device_id).await
+}
+
+/// Get the shuffle and repeat state of the current playback of a user, using the Web API
+pub async fn get_play_mode(token: impl Into<String>) -> Result<(bool, RepeatMode)> {
+  let response = reqwest::Client::new()
+    .get("https://api.spotify.com/v1/me/player")
+    .bearer_auth(token.into())
+    .send()
+    .await?;
+
+  if response.status() != 200 {
+    return Err(anyhow!(
+      "Failed to get playback state: Invalid status code: {}",
+      response.status()
+    ));
+  }
+
+  let body: Value = response.json().await?;
+
+  let shuffle = body["shuffle_state"].as_bool().unwrap_or_default();
+  let repeat = body["repeat_state"]
+    .as_str()
+    .and_then(|state| state.parse().ok())
+    .unwrap_or_default();
+
+  Ok((shuffle, repeat))
+}
+
+/// Get the tracks and episodes that will be played after the current one, using the Web API
+///
+/// Local files and anything else that can't be played are left out. Spotify only returns the next
+/// 20 or so items, not the whole queue.
+pub async fn get_queue(token: impl Into<String>) -> Result<Vec<SpotifyId>> {
+  let response = reqwest::Client::new()
+    .get("https://api.spotify.com/v1/me/player/queue")
+    .bearer_auth(token.into())
+    .send()
+    .await?;
+
+  if response.status() != 200 {
+    return Err(anyhow!(
+      "Failed to get queue: Invalid status code: {}",
+      response.status()
+    ));
+  }
+
+  let body: Value = response.json().await?;
+
+  let queue = body["queue"]
+    .as_array()
+    .map(|queue| {
+      queue
+        .iter()
+        .filter_map(|item| item["uri"].as_str())
+        .filter_map(|uri| SpotifyId::from_uri(uri).ok())
+        .filter(|id| id.audio_type != SpotifyAudioType::NonPlayable)
+        .collect()
+    })
+    .unwrap_or_default();
+
+  Ok(queue)
+}
+
+/// Send a `PUT /me/player/<endpoint>` request for a Spotify Connect device
+async fn control_player(
+  token: impl Into<String>,
+  endpoint: &str,
+  key: &str,
+  value: &str,
+  device_id: &str,
+) -> Result<()> {
+  let response = reqwest::Client::new()
+    .put(format!("https://api.spotify.com/v1/me/player/{endpoint}"))
+    .query(&[(key, value), ("device_id", device_id)])
+    .bearer_auth(token.into())
+    .header(reqwest::header::CONTENT_LENGTH, 0)
+    .send()
+    .await?;
+
+  match response.status() {
+    status if status.is_success() => Ok(()),
+    status => Err(anyhow!(
+      "Failed to {endpoint}: Invalid status code: {}",
+      status
+    )),
+  }
+}
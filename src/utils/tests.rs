@@ -0,0 +1,27 @@
+use super::str_to_time;
+
+#[test]
+fn parses_times() {
+  assert_eq!(str_to_time("42"), Some(42));
+  assert_eq!(str_to_time("1:30"), Some(90));
+  assert_eq!(str_to_time(" 1:02:03 "), Some(3723));
+  assert_eq!(str_to_time("0:00"), Some(0));
+}
+
+#[test]
+fn rejects_invalid_times() {
+  assert_eq!(str_to_time(""), None);
+  assert_eq!(str_to_time("abc"), None);
+  assert_eq!(str_to_time("1:60"), None);
+  assert_eq!(str_to_time("1:60:00"), None);
+  assert_eq!(str_to_time("-1:00"), None);
+  assert_eq!(str_to_time("1:2:3:4"), None);
+}
+
+#[test]
+fn rejects_times_that_overflow() {
+  assert_eq!(str_to_time("99999999:00"), None);
+  assert_eq!(str_to_time("4294967295:59"), None);
+  assert_eq!(str_to_time("9999999:00:00"), None);
+  assert_eq!(str_to_time("1193046:28:15"), Some(u32::MAX));
+}